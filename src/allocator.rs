@@ -1,6 +1,10 @@
 use std::{
-    alloc::Allocator, cell::RefCell, collections::LinkedList, ptr::NonNull
+    cell::RefCell, collections::LinkedList, ptr::NonNull
 };
+#[cfg(feature = "nightly")]
+use std::alloc::Allocator;
+
+use crate::{error::{ReallocError, VallocError}, scope::ScopeGuard, snapshot::{Snapshot, SnapshotDiff, DIFF_BLOCK}, stats::{AllocEvent, CoalesceStats, HeapStats, Leak}, validate::ConsistencyIssue};
 
 // global allocator
 static mut ALLOCATOR:  Option<GlobalValloc> = None;
@@ -19,7 +23,23 @@ impl<'a> GlobalValloc<'a> {
 }
 
 pub fn global_allocator() -> &'static mut GlobalValloc<'static> {
-    unsafe{ ALLOCATOR.as_mut() }.expect("Failed to get global allocator")
+    unsafe{ (*std::ptr::addr_of_mut!(ALLOCATOR)).as_mut() }.expect("Failed to get global allocator")
+}
+
+/// An empty `Vec` that allocates from the global valloc, see `valloc_init`.
+#[cfg(feature = "nightly")]
+pub fn vec_in<T>() -> Vec<T, &'static mut GlobalValloc<'static>> {
+    Vec::new_in(global_allocator())
+}
+
+/// Like `vec_in`, with room for `capacity` elements allocated up front.
+/// 
+/// # Panics
+/// 
+/// Panics if the global valloc doesn't have enough space.
+#[cfg(feature = "nightly")]
+pub fn vec_with_capacity_in<T>(capacity: usize) -> Vec<T, &'static mut GlobalValloc<'static>> {
+    Vec::with_capacity_in(capacity, global_allocator())
 }
 
 impl<'a> From<Valloc<'a>> for GlobalValloc<'a> {
@@ -28,6 +48,7 @@ impl<'a> From<Valloc<'a>> for GlobalValloc<'a> {
     }
 }
 
+#[cfg(feature = "nightly")]
 unsafe impl Allocator for &mut GlobalValloc<'_> {
     fn allocate(&self, layout: std::alloc::Layout) -> Result<std::ptr::NonNull<[u8]>, std::alloc::AllocError> {
         self.0.borrow_mut()
@@ -41,6 +62,181 @@ unsafe impl Allocator for &mut GlobalValloc<'_> {
             .free(SmartPointer::new(ptr))
             .unwrap();
     }
+
+    unsafe fn grow_zeroed(
+        &self, ptr: NonNull<u8>, old_layout: std::alloc::Layout, new_layout: std::alloc::Layout
+    ) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let grown = grow_zeroed_in_place(&mut self.0.borrow_mut(), ptr, old_layout, new_layout);
+        match grown {
+            Some(ptr) => Ok(ptr),
+            None => grow_zeroed_by_copy(self, ptr, old_layout, new_layout),
+        }
+    }
+}
+
+// usable directly on a single thread, it can only be the `#[global_allocator]` behind a wrapper
+// that makes the calls one at a time and sends what the chunk list itself allocates somewhere else,
+// see `tests/global_allocator.rs`
+// unwinding out of these is undefined behavior, so nothing in here may panic
+unsafe impl std::alloc::GlobalAlloc for GlobalValloc<'_> {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let Ok(mut valloc) = self.0.try_borrow_mut() else { return std::ptr::null_mut(); };
+        alloc_aligned::<u8>(&mut valloc, layout.size(), layout.align())
+            .map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    // a pointer that isn't ours means the heap is already corrupted, there is no way to report it
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: std::alloc::Layout) {
+        let Ok(mut valloc) = self.0.try_borrow_mut() else { std::process::abort(); };
+        if valloc.free(SmartPointer::new_unchecked(ptr)).is_err() { std::process::abort(); }
+    }
+
+    // resizes in place whenever it can instead of always moving like the default
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        let Ok(mut valloc) = self.0.try_borrow_mut() else { return std::ptr::null_mut(); };
+        let old = SmartPointer::<u8>::new_unchecked(ptr);
+        if new_size <= layout.size() && valloc.shrink_in_place(&old, new_size).is_ok() { return ptr; }
+        if valloc.grow_in_place(&old, new_size) { return ptr; }
+        if !valloc.owns(&old) { return std::ptr::null_mut(); }
+
+        let Ok(new) = alloc_aligned::<u8>(&mut valloc, new_size, layout.align()) else { return std::ptr::null_mut(); };
+        std::ptr::copy_nonoverlapping(ptr, new.as_ptr(), layout.size().min(new_size));
+        match valloc.relocated(old, &new) {
+            Ok(()) => new.as_ptr(),
+            Err(_) => {
+                let _ = valloc.free(new);
+                std::ptr::null_mut()
+            },
+        }
+    }
+}
+
+#[cfg(feature = "nightly")]
+/// The in place part of `Allocator::grow_zeroed`, only the newly added bytes are zeroed.
+fn grow_zeroed_in_place(
+    valloc: &mut Valloc, ptr: NonNull<u8>, old_layout: std::alloc::Layout, new_layout: std::alloc::Layout
+) -> Option<NonNull<[u8]>> {
+    if !(ptr.as_ptr() as usize).is_multiple_of(new_layout.align()) { return None; }
+    if !valloc.grow_in_place(&SmartPointer::new(ptr), new_layout.size()) { return None; }
+
+    unsafe { ptr.as_ptr().add(old_layout.size()).write_bytes(0, new_layout.size() - old_layout.size()); }
+    Some(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+}
+
+#[cfg(feature = "nightly")]
+/// What `Allocator::grow_zeroed` does by default, move the data to a new zeroed allocation.
+unsafe fn grow_zeroed_by_copy<A: Allocator + ?Sized>(
+    allocator: &A, ptr: NonNull<u8>, old_layout: std::alloc::Layout, new_layout: std::alloc::Layout
+) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+    let new_ptr = allocator.allocate_zeroed(new_layout)?;
+    std::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr() as *mut u8, old_layout.size());
+    allocator.deallocate(ptr, old_layout);
+    Ok(new_ptr)
+}
+
+#[cfg(feature = "nightly")]
+/// A handle to the global allocator that skips the `RefCell` borrow on every call.
+/// 
+/// This is only sound when the global allocator is used from a single thread.
+/// In debug builds the handle remembers the thread that created it
+/// and panics if it is used from any other thread.
+#[derive(Debug, Clone, Copy)]
+pub struct UnsyncGlobalValloc {
+    allocator: *mut Valloc<'static>,
+    #[cfg(debug_assertions)]
+    owner: std::thread::ThreadId,
+}
+
+#[cfg(feature = "nightly")]
+// the handle can be moved around so that the debug check can catch it being used on the wrong thread
+unsafe impl Send for UnsyncGlobalValloc {}
+
+#[cfg(feature = "nightly")]
+impl UnsyncGlobalValloc {
+    #[allow(clippy::mut_from_ref)]
+    fn valloc(&self) -> &mut Valloc<'static> {
+        #[cfg(debug_assertions)]
+        if self.owner != std::thread::current().id() {
+            panic!(
+                "UnsyncGlobalValloc used from a second thread! Created on {:?}, used on {:?}",
+                self.owner, std::thread::current().id()
+            );
+        }
+        unsafe { &mut *self.allocator }
+    }
+}
+
+#[cfg(feature = "nightly")]
+/// Get a handle to the global allocator without any borrow checking.
+/// 
+/// # Safety
+/// 
+/// For as long as the handle (or any copy of it, or any collection using it) is alive,
+/// the global allocator must only be used from the thread that called this function,
+/// and never through `global_allocator` or `get_allocator` while the handle is in the middle of a call.
+/// Only debug builds check the thread, in release builds breaking this is a data race.
+/// 
+/// # Panics
+/// 
+/// Panics if the global allocator is not initialized
+pub unsafe fn global_allocator_unsync() -> UnsyncGlobalValloc {
+    UnsyncGlobalValloc {
+        allocator: global_allocator().0.as_ptr(),
+        #[cfg(debug_assertions)]
+        owner: std::thread::current().id(),
+    }
+}
+
+#[cfg(feature = "nightly")]
+unsafe impl Allocator for UnsyncGlobalValloc {
+    fn allocate(&self, layout: std::alloc::Layout) -> Result<std::ptr::NonNull<[u8]>, std::alloc::AllocError> {
+        self.valloc()
+            .alloc(layout.size())
+            .map(|ptr: SmartPointer<[u8]>| ptr.non_null_ptr())
+            .map_err(|_| std::alloc::AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, _layout: std::alloc::Layout) {
+        self.valloc()
+            .free(SmartPointer::new(ptr))
+            .unwrap();
+    }
+
+    unsafe fn grow_zeroed(
+        &self, ptr: NonNull<u8>, old_layout: std::alloc::Layout, new_layout: std::alloc::Layout
+    ) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        match grow_zeroed_in_place(self.valloc(), ptr, old_layout, new_layout) {
+            Some(ptr) => Ok(ptr),
+            None => grow_zeroed_by_copy(self, ptr, old_layout, new_layout),
+        }
+    }
+}
+
+/// How `realloc` rebuilds the pointer to a chunk that was resized to `size` bytes.
+/// 
+/// Sized types have no metadata so the pointer is only moved,
+/// slices get their length recomputed from the new size.
+/// `ALIGN` is what a chunk that is moved has to be aligned to.
+pub trait Resizable {
+    const ALIGN: usize;
+
+    fn resized(ptr: NonNull<u8>, size: usize) -> NonNull<Self>;
+}
+
+impl<T> Resizable for T {
+    const ALIGN: usize = std::mem::align_of::<T>();
+
+    fn resized(ptr: NonNull<u8>, _size: usize) -> NonNull<Self> {
+        ptr.cast()
+    }
+}
+
+impl<T> Resizable for [T] {
+    const ALIGN: usize = std::mem::align_of::<T>();
+
+    fn resized(ptr: NonNull<u8>, size: usize) -> NonNull<Self> {
+        NonNull::slice_from_raw_parts(ptr.cast(), size / std::mem::size_of::<T>().max(1))
+    }
 }
 
 // convenience type for a pointer
@@ -79,6 +275,16 @@ impl<T> SmartPointer<T>
         Self {ptr: NonNull::new_unchecked(ptr)}
     }
 
+    /// Checked version of new for raw pointers
+    /// 
+    /// # Returns
+    /// 
+    /// * `Some(SmartPointer<T>)` - The wrapped pointer
+    /// * `None` - If the pointer is null
+    pub fn try_new(ptr: *mut T) -> Option<Self> {
+        NonNull::new(ptr).map(Self::new)
+    }
+
     pub fn as_ptr(&self) -> *mut T {
         self.ptr.as_ptr()
     }
@@ -124,6 +330,108 @@ impl<T> std::ops::IndexMut<usize> for SmartPointer<T> {
     }
 }
 
+impl<T> SmartPointer<T> {
+    /// A pointer that is not null but doesn't point to anything, to stand in for a missing pointer.
+    /// 
+    /// It is well aligned for `T` but must never be read from or written to.
+    pub fn dangling() -> Self {
+        Self::new(NonNull::dangling())
+    }
+
+    /// Writes a value to the pointed to memory without dropping whatever was there before.
+    /// 
+    /// Unlike `*ptr = value` this is fine to use on freshly allocated (uninitialized) memory.
+    pub fn write(&mut self, value: T) {
+        unsafe { self.ptr.as_ptr().write(value) }
+    }
+
+    /// Reads a copy of the pointed to value.
+    pub fn read(&self) -> T 
+        where T: Copy
+    {
+        unsafe { self.ptr.as_ptr().read() }
+    }
+
+    /// Same as `write` but for the element at `index`, treating the pointer as an array.
+    /// 
+    /// Just like indexing, this is not bounds checked!
+    pub fn write_at(&mut self, index: usize, value: T) {
+        unsafe { self.ptr.as_ptr().add(index).write(value) }
+    }
+
+    /// Same as `read` but for the element at `index`, treating the pointer as an array.
+    /// 
+    /// Just like indexing, this is not bounds checked!
+    pub fn read_at(&self, index: usize) -> T 
+        where T: Copy
+    {
+        unsafe { self.ptr.as_ptr().add(index).read() }
+    }
+}
+
+impl<T> SmartPointer<[T]> {
+    /// Writes a value to the first element without dropping whatever was there before.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the slice is empty
+    pub fn write(&mut self, value: T) {
+        self.write_at(0, value);
+    }
+
+    /// Reads a copy of the first element.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the slice is empty
+    pub fn read(&self) -> T 
+        where T: Copy
+    {
+        self.read_at(0)
+    }
+
+    /// Writes a value to the element at `index` without dropping whatever was there before.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `index` is out of bounds
+    pub fn write_at(&mut self, index: usize, value: T) {
+        let len = self.ptr.len();
+        assert!(index < len, "Index out of bounds: the len is {len} but the index is {index}");
+        unsafe { self.ptr.cast::<T>().as_ptr().add(index).write(value) }
+    }
+
+    /// Reads a copy of the element at `index`.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `index` is out of bounds
+    pub fn read_at(&self, index: usize) -> T 
+        where T: Copy
+    {
+        let len = self.ptr.len();
+        assert!(index < len, "Index out of bounds: the len is {len} but the index is {index}");
+        unsafe { self.ptr.cast::<T>().as_ptr().add(index).read() }
+    }
+
+    /// Copies `src` into the start of the slice, like `<[T]>::copy_from_slice` but a shorter `src` is fine.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(())` - If `src` was copied.
+    /// * `Err(VallocError)` - If `src` is longer than the slice, nothing was copied.
+    pub fn copy_from_slice(&mut self, src: &[T]) -> Result<(), VallocError> 
+        where T: Copy
+    {
+        let capacity = self.ptr.len();
+        if src.len() > capacity {
+            return Err(VallocError::SliceTooLong { len: src.len(), capacity });
+        }
+        unsafe { std::ptr::copy(src.as_ptr(), self.ptr.cast::<T>().as_ptr(), src.len()); }
+        Ok(())
+    }
+}
+
 #[no_mangle]
 /// Get a mutable reference to the global allocator
 /// 
@@ -147,7 +455,7 @@ impl<T> std::ops::IndexMut<usize> for SmartPointer<T> {
 /// This function will panic if the allocator is not initialized
 pub fn get_allocator() -> &'static mut Valloc<'static> {
     pub unsafe fn get_allocator() -> Result<&'static mut Valloc<'static>, &'static str> {
-        match ALLOCATOR {
+        match *std::ptr::addr_of_mut!(ALLOCATOR) {
             Some(ref mut allocator) => Ok(allocator.0.get_mut()),
             None => Err("Allocator not initialized!")
         }
@@ -163,27 +471,28 @@ pub fn get_allocator() -> &'static mut Valloc<'static> {
 /// * `msize` - The total memory size to allocate
 pub fn valloc_init(msize: usize) {
     #[cfg(debug_assertions)]
-    if unsafe{ALLOCATOR.is_some()} { panic!("Allocator already initialized!"); }
+    if unsafe{(*std::ptr::addr_of!(ALLOCATOR)).is_some()} { panic!("Allocator already initialized!"); }
     #[cfg(debug_assertions)]
-    if unsafe{GLOBAL_MEM.is_some()} { panic!("Memory already initialized!"); }
+    if unsafe{(*std::ptr::addr_of!(GLOBAL_MEM)).is_some()} { panic!("Memory already initialized!"); }
     
 
     unsafe { GLOBAL_MEM = Some(vec![0u8; msize].leak()); }
-    unsafe { ALLOCATOR = Some(GlobalValloc::new(Valloc::new(GLOBAL_MEM.as_deref_mut().unwrap()))); }
+    unsafe { ALLOCATOR = Some(GlobalValloc::new(Valloc::new((*std::ptr::addr_of_mut!(GLOBAL_MEM)).as_deref_mut().unwrap()))); }
 }
 
 #[no_mangle]
 pub extern "C" fn global_init_memory(mem: *mut (), len: usize) {
     #[cfg(debug_assertions)]
-    if unsafe{GLOBAL_MEM.is_some()} { panic!("Memory already initialized!"); }
+    if unsafe{(*std::ptr::addr_of!(GLOBAL_MEM)).is_some()} { panic!("Memory already initialized!"); }
 
     unsafe { GLOBAL_MEM = Some(std::slice::from_raw_parts_mut(mem.cast(), len)); }
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn global_init_allocator(allocator: *mut Valloc<'static>) {
     #[cfg(debug_assertions)]
-    if unsafe{ALLOCATOR.is_some()} { panic!("Allocator already initialized!"); }
+    if unsafe{(*std::ptr::addr_of!(ALLOCATOR)).is_some()} { panic!("Allocator already initialized!"); }
 
     unsafe { ALLOCATOR = Some(GlobalValloc::from_ptr(allocator.as_mut().unwrap())); }
 }
@@ -206,6 +515,219 @@ pub struct Valloc<'a> {
     memory: &'a [u8],
 
     chunks: ChunkList, 
+
+    coalesce_on_free: bool,
+    // `free` runs `compact_free_list` once fragmentation goes above this
+    auto_compact: Option<f64>,
+
+    backing: Backing,
+
+    counters: Counters,
+
+    recent_frees: RecentFrees,
+
+    name: Option<String>,
+
+    watermark: Option<Watermark>,
+
+    observer: Option<Observer>,
+
+    // the size of the chunk at the end of the memory that is never handed out
+    guard: usize,
+
+    // the size of the chunk at the start of the memory that holds the chunk list, see `new_in_place`
+    header: usize,
+
+    // in deterministic mode everything is allocated at or after the frontier (an offset into the memory)
+    deterministic: bool,
+    frontier: usize,
+
+    // every chunk handed out starts on and is a multiple of this, 1 for byte granularity
+    page: usize,
+
+    frozen: bool,
+
+    // every chunk handed out by `alloc` is zeroed first
+    zero_on_alloc: bool,
+
+    // how many bytes the last `realloc` had to copy, so the tests can check the copy is clamped
+    #[cfg(test)]
+    last_realloc_copied: usize,
+}
+
+/// A callback for when the free memory of an allocator drops below `bytes`.
+/// 
+/// Clones of an allocator share the same callback.
+#[derive(Clone)]
+struct Watermark {
+    bytes: usize,
+    callback: std::rc::Rc<RefCell<dyn FnMut(usize)>>,
+    // set once the callback fired, cleared when the free memory is back above the watermark
+    below: bool,
+}
+
+impl std::fmt::Debug for Watermark {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watermark").field("bytes", &self.bytes).field("below", &self.below).finish_non_exhaustive()
+    }
+}
+
+/// A callback for every allocation, free and reallocation.
+/// 
+/// Clones of an allocator share the same callback.
+#[derive(Clone)]
+struct Observer(std::rc::Rc<RefCell<dyn FnMut(AllocEvent)>>);
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Observer").finish_non_exhaustive()
+    }
+}
+
+/// Running totals kept by the allocator for `stats()`.
+#[derive(Debug, Clone, Default)]
+struct Counters {
+    total_allocated: usize,
+    total_freed: usize,
+    peak_used: usize,
+    relocations: usize,
+    allocations: u64,
+    merges_forward: usize,
+    merges_backward: usize,
+    frees_without_merge: usize,
+    // chunks looked at by the last `alloc` and by all of them together
+    last_search_len: usize,
+    total_scanned: usize,
+}
+
+const RECENT_FREES: usize = 32;
+
+/// The start of the last few chunks that were freed, so a double free can still be told apart
+/// from a pointer that was never allocated once the freed chunk was merged into the one before it.
+/// 
+/// A fixed ring so `free` never has to allocate, 0 marks an empty slot.
+#[derive(Debug, Clone, Default)]
+struct RecentFrees {
+    addrs: [usize; RECENT_FREES],
+    next: usize,
+}
+
+impl RecentFrees {
+    fn note(&mut self, addr: usize) {
+        self.addrs[self.next] = addr;
+        self.next = (self.next + 1) % RECENT_FREES;
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        addr != 0 && self.addrs.contains(&addr)
+    }
+
+    // the memory in `[start, end)` was handed out again, a free of those addresses isn't a double free anymore
+    fn forget(&mut self, start: usize, end: usize) {
+        self.addrs.iter_mut().filter(|x| (start..end).contains(*x)).for_each(|x| *x = 0);
+    }
+}
+
+impl Counters {
+    fn next_allocation_id(&mut self) -> u64 {
+        self.allocations += 1;
+        self.allocations
+    }
+
+    fn note_search(&mut self, scanned: usize) {
+        self.last_search_len = scanned;
+        self.total_scanned += scanned;
+    }
+}
+
+// the header written by `Valloc::new_in_place` is the magic, the capacity and the number of chunks,
+// followed by `(offset, size, in_use)` for every chunk, all as little endian u64s
+const HEADER_MAGIC: u64 = u64::from_le_bytes(*b"VALLOC01");
+const HEADER_PREFIX: usize = 24;
+const HEADER_ENTRY: usize = 24;
+// the fewest chunks the header of `Valloc::new_in_place` has room for
+const MIN_HEADER_CHUNKS: usize = 16;
+
+/// Where the memory managed by a `Valloc` comes from.
+#[derive(Debug, Clone, Default)]
+pub enum Backing {
+    /// The memory is borrowed from the caller.
+    #[default]
+    Borrowed,
+    /// The memory is an anonymous mapping owned by the allocator,
+    /// it is unmapped once the last allocator using it is dropped.
+    #[cfg(all(unix, feature = "mmap"))]
+    Mmap(std::sync::Arc<crate::mmap::MmapRegion>),
+    /// The memory of several allocators joined with `Valloc::merge_with`,
+    /// as `(len, backing)` in address order. Every part is kept alive as long as the allocator.
+    Merged(Vec<(usize, Backing)>),
+    /// A heap buffer owned by the allocator, freed once the last allocator using it is dropped.
+    Heap(std::sync::Arc<HeapBuffer>),
+}
+
+/// A heap allocated buffer that an allocator hands out memory from.
+#[derive(Debug)]
+pub struct HeapBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// the buffer is plain memory that nothing else has a handle to
+unsafe impl Send for HeapBuffer {}
+unsafe impl Sync for HeapBuffer {}
+
+impl HeapBuffer {
+    /// Copies `data` into a new heap buffer of the same length.
+    pub fn copy_of(data: &[u8]) -> Self {
+        let len = data.len();
+        let ptr = Box::into_raw(Box::<[u8]>::from(data)) as *mut u8;
+        Self { ptr: NonNull::new(ptr).expect("Box returned null!"), len }
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for HeapBuffer {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len)) });
+    }
+}
+
+impl Backing {
+    // touches the pages of `[offset, offset + len)` for the parts that are mapped
+    fn prefault(&self, offset: usize, len: usize) {
+        match self {
+            #[cfg(all(unix, feature = "mmap"))]
+            Backing::Mmap(region) => region.prefault(offset, len),
+            Backing::Merged(parts) => {
+                let mut start = 0;
+                for (size, part) in parts {
+                    let (from, to) = (offset.max(start), offset.saturating_add(len).min(start + size));
+                    if from < to { part.prefault(from - start, to - from); }
+                    start += size;
+                }
+            },
+            _ => { let _ = (offset, len); },
+        }
+    }
+
+    // the parts of this backing as they would appear in a merged one
+    fn into_parts(self, len: usize) -> Vec<(usize, Backing)> {
+        match self {
+            Backing::Merged(parts) => parts,
+            backing => vec![(len, backing)],
+        }
+    }
 }
 
 impl From<&[u8]> for Valloc<'_> {
@@ -215,9 +737,20 @@ impl From<&[u8]> for Valloc<'_> {
     }
 }
 
+/// An allocator without any memory, every allocation fails with `OutOfMemory`.
+/// 
+/// Useful as a placeholder for a field that gets its real allocator later.
+impl Default for Valloc<'_> {
+    fn default() -> Self {
+        Self::from_parts(&[], ChunkList::new(None, 0))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ChunkList {
     list: LinkedList< Box<ChunkNode> >,
+    // the nodes of chunks that were merged away, reused for new chunks so splitting doesn't allocate
+    spare: LinkedList< Box<ChunkNode> >,
     available: usize,
 }
 
@@ -227,14 +760,14 @@ impl ChunkList {
         if let Some(start) = start {
             list.push_back(start);
         }
-        Self { list, available }
+        Self { list, spare: LinkedList::new(), available }
     }
 
-    pub fn iter(&self) -> std::collections::linked_list::Iter< Box<ChunkNode> > {
+    pub fn iter(&self) -> std::collections::linked_list::Iter<'_, Box<ChunkNode> > {
         self.list.iter()
     }
 
-    pub fn iter_mut(&mut self) -> std::collections::linked_list::IterMut< Box<ChunkNode> > {
+    pub fn iter_mut(&mut self) -> std::collections::linked_list::IterMut<'_, Box<ChunkNode> > {
         self.list.iter_mut()
     }
 
@@ -258,23 +791,217 @@ impl ChunkList {
         self.available
     }
 
+    /// Panics if the chunks are not in ascending address order.
+    /// 
+    /// `alloc` and `free` run this in debug builds, since merging neighbours relies on the order.
+    pub fn assert_sorted(&self) {
+        let mut prev: Option<&ChunkNode> = None;
+        for chunk in self.list.iter() {
+            if let Some(prev) = prev {
+                assert!(
+                    prev.ptr <= chunk.ptr,
+                    "Chunk list is out of order! {:p} comes before {:p}", prev.ptr, chunk.ptr
+                );
+            }
+            prev = Some(chunk);
+        }
+    }
+
+    /// Inserts a chunk at the given position in the list.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `index` is greater than the length of the list
+    pub fn insert(&mut self, index: usize, chunk: Box<ChunkNode>) {
+        let mut tail = self.list.split_off(index);
+        self.list.push_back(chunk);
+        self.list.append(&mut tail);
+    }
+
+    /// Inserts a new chunk at the given position in the list.
+    /// 
+    /// Unlike `insert` this reuses the node of a chunk that was merged away if there is one,
+    /// so it only allocates when the list is longer than it has ever been.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `index` is greater than the length of the list
+    pub fn insert_new(&mut self, index: usize, chunk: ChunkNode) {
+        let mut node = match self.spare.len() {
+            0 => LinkedList::from([Box::new(chunk)]),
+            len => {
+                let mut node = self.spare.split_off(len - 1);
+                **node.front_mut().expect("The spare node was just split off!") = chunk;
+                node
+            },
+        };
+        let mut tail = self.list.split_off(index);
+        self.list.append(&mut node);
+        self.list.append(&mut tail);
+    }
+
+    /// Removes the chunk at the given position in the list and keeps its node for `insert_new`.
+    /// 
+    /// # Returns
+    /// 
+    /// * `usize` - The size of the removed chunk
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `index` is out of bounds
+    pub fn discard(&mut self, index: usize) -> usize {
+        let mut tail = self.list.split_off(index);
+        let mut rest = tail.split_off(1);
+        let size = tail.front().expect("Chunk index out of bounds!").size;
+        self.spare.append(&mut tail);
+        self.list.append(&mut rest);
+        size
+    }
+
+    // moves the first node of `list` to the spare nodes, linked lists only allocate when a value is pushed
+    fn spare_front(&mut self, list: &mut LinkedList<Box<ChunkNode>>) {
+        let rest = list.split_off(1);
+        self.spare.append(&mut std::mem::replace(list, rest));
+    }
+
+    /// Removes the chunk at the given position in the list.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `index` is out of bounds
+    pub fn remove(&mut self, index: usize) -> Box<ChunkNode> {
+        let mut tail = self.list.split_off(index);
+        let chunk = tail.pop_front().expect("Chunk index out of bounds!");
+        self.list.append(&mut tail);
+        chunk
+    }
+
+    /// Grows the chunk at `index` by `extra` bytes, taken from the front of the chunk after it.
+    /// 
+    /// This only happens if the next chunk is free, next to it in memory and big enough.
+    /// The available size is not updated.
+    /// 
+    /// # Returns
+    /// 
+    /// * `true` - If the chunk was grown
+    pub fn grow_into_next(&mut self, index: usize, extra: usize) -> bool {
+        let mut tail = self.list.split_off(index + 1);
+        let grown = match (self.list.back_mut(), tail.front_mut()) {
+            (Some(chunk), Some(next)) if !next.in_use && next.size >= extra 
+                && chunk.ptr.wrapping_add(chunk.size) == next.ptr => {
+                chunk.size += extra;
+                next.ptr = next.ptr.wrapping_add(extra);
+                next.size -= extra;
+                true
+            },
+            _ => false
+        };
+        if tail.front().is_some_and(|x| x.size == 0) { self.spare_front(&mut tail); }
+        self.list.append(&mut tail);
+        grown
+    }
+
+    /// Merges the chunk at `index` with the chunk after it
+    /// if both are free and next to each other in memory.
+    /// 
+    /// # Returns
+    /// 
+    /// * `true` - If the chunks were merged
+    pub fn merge_next(&mut self, index: usize) -> bool {
+        let mut tail = self.list.split_off(index + 1);
+        let merged = match (self.list.back_mut(), tail.front()) {
+            (Some(chunk), Some(next)) if !chunk.in_use && !next.in_use 
+                && chunk.ptr.wrapping_add(chunk.size) == next.ptr => {
+                chunk.size += next.size;
+                true
+            },
+            _ => false
+        };
+        if merged { self.spare_front(&mut tail); }
+        self.list.append(&mut tail);
+        merged
+    }
+
+    /// Merges the chunk at `index` into the chunk before it in the list
+    /// if both are free and the chunk before ends right where it starts.
+    /// 
+    /// # Returns
+    /// 
+    /// * `true` - If the chunks were merged, the chunk at `index` is gone then
+    pub fn merge_prev(&mut self, index: usize) -> bool {
+        if index == 0 { return false; }
+        let mut chunks = self.list.iter_mut().skip(index - 1);
+        let (Some(prev), Some(chunk)) = (chunks.next(), chunks.next()) else { return false; };
+        if prev.in_use || chunk.in_use || prev.ptr.wrapping_add(prev.size) != chunk.ptr { return false; }
+        prev.size += chunk.size;
+        self.discard(index);
+        true
+    }
+
     pub fn set_available(&mut self, available: usize) {
         self.available = available;
     }
 }
 
+/// A chunk as plain data, with its position as an offset from the start of the memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct ChunkRepr {
+    pub offset: usize,
+    pub size: usize,
+    pub in_use: bool,
+}
+
+/// A chunk in offset space, as returned by `Valloc::chunk_at_offset`.
+pub type Span = ChunkRepr;
+
+/// The chunk list of a `Valloc` without any pointers in it,
+/// so it can be stored or passed around and turned back into an allocator with `Valloc::try_from_raw_parts`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChunkListRepr {
+    /// The chunks in address order.
+    pub chunks: Vec<ChunkRepr>,
+}
+
+// `ptr_unsized` reads `ptr` and `size` as a fat pointer, so they must stay first and in this order
 #[derive(Debug, Clone)]
+#[repr(C)]
 pub struct ChunkNode {
     ptr: *mut u8,
     size: usize,
-    in_use: bool
+    in_use: bool,
+    // the alignment the chunk was allocated with, kept when it is moved by a compaction
+    align: usize,
+    // stamped on every allocation, lower ids are older
+    allocation_id: u64,
+    // set by `Valloc::alloc_tagged`, kept when the allocation is moved
+    tag: Option<&'static str>,
+    // where the chunk was allocated, resolved lazily by `Valloc::leak_check`
+    #[cfg(feature = "backtrace")]
+    backtrace: Option<backtrace::Backtrace>,
 }
 
 impl ChunkNode {
     pub fn new(ptr: *mut u8, size: usize, in_use: bool) -> Self {
         // upon creation, the chunk is in use
         // and when free is called, it will be set to false
-        Self { ptr, size, in_use }
+        Self { 
+            ptr, size, in_use, align: 1, allocation_id: 0, tag: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: None,
+        }
+    }
+
+    /// Create a chunk `offset` bytes into the memory starting at `base`.
+    pub fn from_offset(base: *mut u8, offset: usize, size: usize, in_use: bool) -> Self {
+        Self::new(base.wrapping_add(offset), size, in_use)
+    }
+
+    /// The position of the chunk in the memory starting at `base`.
+    /// 
+    /// The offset is computed from the pointer, so it is always up to date with moves and splits.
+    pub fn offset_from(&self, base: *const u8) -> usize {
+        self.ptr as usize - base as usize
     }
 
     pub fn get_ptr<T: Sized>(&self) -> *mut T {
@@ -284,72 +1011,1821 @@ impl ChunkNode {
     pub fn ptr_unsized<T: ?Sized>(&self) -> &*mut T {
         unsafe{ std::mem::transmute(&self.ptr) }
     }
+
+    fn smart_pointer<T: ?Sized>(&self) -> SmartPointer<T> {
+        SmartPointer::new(
+            NonNull::new(*self.ptr_unsized::<T>()).expect("Failed to create SmartPointer!")
+        )
+    }
     
     pub fn get_size(&self) -> usize {
         self.size
     }
-}
 
-impl<'a> Valloc<'a> {
-    /// Create a new Kernel instance from existing memory.
-    /// 
+    pub fn is_in_use(&self) -> bool {
+        self.in_use
+    }
+
+    /// The alignment the chunk was allocated with, 1 for chunks that weren't made by an allocation.
+    pub fn get_align(&self) -> usize {
+        self.align
+    }
+
+    /// The id of the allocation that last used this chunk, lower ids are older.
+    pub fn get_allocation_id(&self) -> u64 {
+        self.allocation_id
+    }
+
+    pub fn get_tag(&self) -> Option<&'static str> {
+        self.tag
+    }
+
+    pub fn set_size(&mut self, size: usize) {
+        self.size = size;
+    }
+
+    pub fn set_in_use(&mut self, in_use: bool) {
+        self.in_use = in_use;
+    }
+}
+
+impl<'a> Valloc<'a> {
+    /// Create a new Kernel instance from existing memory.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `memory` - The existing memory to be used by the Kernel.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use valloc::allocator::Valloc;
+    /// let kernel = Valloc::new(vec![0u8; 1024].leak());
+    /// ```
+    pub fn new(memory: &'a mut [u8]) -> Self {
+        // we need to cast the memory to a u8 pointer
+        // we also must adjust the len to be in bytes (which is len * `the difference between size_of::<T>() and size_of::<u8>()`)
+        let len = memory.len();
+        let chunks = {
+            let memory = memory.as_mut().as_mut_ptr();
+            ChunkList::new(
+                Some(
+                    Box::new(ChunkNode::new(
+                        memory, 
+                        len, 
+                        false
+                    ))
+                ),
+                len
+            )
+        };
+
+        /*our heap chunk starts out spanning the entire memory*/
+        Self::from_parts(memory, chunks)
+    }
+
+    /// Create a new Valloc instance that keeps a copy of its chunk list at the front of `memory`,
+    /// so the whole state of the allocator can be saved or shared along with the buffer.
+    /// 
+    /// The chunks themselves are still tracked on the heap, the header is a mirror of them
+    /// that is rewritten on every change, so a copy of the memory can be reopened with `open_in_place` at any time.
+    /// It is a chunk in use at offset 0 that is never handed out, freed or reported as live,
+    /// with room for an eighth of the memory worth of chunks (and at least 16),
+    /// see `new_in_place_with_capacity` to choose the number.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `memory` is too small to hold the header and still have room left to allocate.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use valloc::allocator::Valloc;
+    /// let mut memory = vec![0u8; 1024];
+    /// let mut allocator = Valloc::new_in_place(&mut memory);
+    /// let ptr = allocator.alloc::<u8>(16).unwrap();
+    /// 
+    /// let mut copy = allocator.memory().to_vec();
+    /// let reopened = Valloc::open_in_place(&mut copy).unwrap();
+    /// assert_eq!(reopened.available(), allocator.available());
+    /// ```
+    pub fn new_in_place(memory: &'a mut [u8]) -> Self {
+        let capacity = ((memory.len() / 8).saturating_sub(HEADER_PREFIX) / HEADER_ENTRY).max(MIN_HEADER_CHUNKS);
+        Self::new_in_place_with_capacity(memory, capacity)
+    }
+
+    /// Same as `new_in_place`, but the header has room for exactly `chunks` chunks.
+    /// 
+    /// Every allocation adds at most two chunks (itself and the free space before it),
+    /// so `2 * allocations + 2` is always enough. A chunk list that outgrows the header
+    /// is reported by `validate_all` and can't be reopened with `open_in_place`.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if `memory` is too small to hold the header and still have room left to allocate.
+    pub fn new_in_place_with_capacity(memory: &'a mut [u8], chunks: usize) -> Self {
+        let header = chunks.checked_mul(HEADER_ENTRY).and_then(|x| x.checked_add(HEADER_PREFIX));
+        let header = header.filter(|&x| x < memory.len())
+            .unwrap_or_else(|| panic!("Memory is too small for an allocator header => Size: {}, Chunks: {chunks}", memory.len()));
+
+        let mut valloc = Valloc::new(memory);
+        valloc.mark_in_use(0, header).expect("A new allocator is all free!");
+        valloc.write_at_offset(0, HEADER_MAGIC.to_le_bytes()).expect("The header was just reserved!");
+        valloc.write_at_offset(8, (chunks as u64).to_le_bytes()).expect("The header was just reserved!");
+        valloc.header = header;
+        valloc.sync_header();
+        valloc
+    }
+
+    /// Rebuild an allocator over `memory` from the header written by `new_in_place`.
+    /// 
+    /// The header is not trusted, the chunk list in it is validated like in `try_from_raw_parts`.
+    /// The reopened allocator keeps the header up to date just like the original one.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(Valloc)` - The allocator as it was after its last change.
+    /// * `Err(VallocError)` - `MissingHeader` if there is no header, `HeaderFull` if the chunk list outgrew it,
+    ///   or `Inconsistent` if the chunk list in it is not valid.
+    pub fn open_in_place(memory: &'a mut [u8]) -> Result<Self, VallocError> {
+        let mut valloc = Valloc::new(memory);
+        let capacity = valloc.header_capacity()?;
+        let count = u64::from_le_bytes(valloc.read_at_offset(16)?) as usize;
+        if count > capacity { return Err(VallocError::HeaderFull { chunks: count, capacity }); }
+
+        let chunks = (0..count).map(|i| {
+            let at = HEADER_PREFIX + i * HEADER_ENTRY;
+            let [offset, size, in_use]: [[u8; 8]; 3] = valloc.read_at_offset(at)?;
+            Ok(ChunkRepr {
+                offset: u64::from_le_bytes(offset) as usize,
+                size: u64::from_le_bytes(size) as usize,
+                in_use: u64::from_le_bytes(in_use) != 0,
+            })
+        }).collect::<Result<Vec<_>, VallocError>>()?;
+        // the header has to describe itself as the first chunk
+        let header = HEADER_PREFIX + capacity * HEADER_ENTRY;
+        if chunks.iter().min_by_key(|x| x.offset) != Some(&ChunkRepr { offset: 0, size: header, in_use: true }) {
+            return Err(VallocError::MissingHeader);
+        }
+        valloc.replace_chunks(&ChunkListRepr { chunks })?;
+        valloc.header = header;
+        Ok(valloc)
+    }
+
+    /// Hands every chunk in use of this allocator over to `target`, which has to manage the exact same memory,
+    /// e.g. after setting things up with a temporary allocator over the memory of the global one.
+    /// 
+    /// Nothing is moved, the chunks just become chunks in use of `target` and can be freed there.
+    /// Either all of them are handed over or none are.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(())` - If `target` now tracks every chunk that was in use here.
+    /// * `Err(VallocError)` - If the memory differs, `target` is frozen, or a chunk overlaps one in use in `target`.
+    pub fn try_merge_into(self, target: &mut Valloc<'_>) -> Result<(), VallocError> {
+        if self.memory.as_ptr_range() != target.memory.as_ptr_range() { return Err(VallocError::BackingMismatch); }
+        if target.frozen { return Err(VallocError::Frozen); }
+
+        let base = self.memory.as_ptr() as usize;
+        let live: Vec<(usize, usize)> = self.chunks.iter()
+            .filter(|x| x.in_use && !self.is_reserved(x.ptr))
+            .map(|x| (x.ptr as usize - base, x.size))
+            .collect();
+        // everything is checked up front so a failure leaves `target` as it was
+        for &(offset, size) in &live {
+            let blocker = target.chunks.iter().find(|x| {
+                let start = x.ptr as usize - base;
+                x.in_use && start < offset + size && offset < start + x.size
+            });
+            if let Some(blocker) = blocker {
+                return Err(VallocError::DestinationInUse { at: blocker.ptr as usize - base });
+            }
+        }
+        for (offset, size) in live {
+            target.mark_in_use(offset, size)?;
+        }
+        Ok(())
+    }
+
+    /// Same as `try_merge_into` with the global allocator as the target.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the global allocator is not initialized.
+    pub fn try_merge_into_global(self) -> Result<(), VallocError> {
+        self.try_merge_into(get_allocator())
+    }
+
+    /// Run `f` with a child allocator over a fresh `size` bytes region of this one,
+    /// the whole region is freed again once `f` returns.
+    /// 
+    /// Nothing allocated from the child may be used after `f` returns.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(R)` - What `f` returned.
+    /// * `Err(VallocError)` - The reason the region could not be allocated, `f` is not run then.
+    pub fn with_region<R>(&mut self, size: usize, f: impl FnOnce(&mut Valloc) -> R) -> Result<R, VallocError> {
+        let region = self.alloc::<[u8]>(size)?;
+        let result = {
+            let memory = unsafe { std::slice::from_raw_parts_mut(region.as_ptr() as *mut u8, size) };
+            f(&mut Valloc::new(memory))
+        };
+        self.free(region)?;
+        Ok(result)
+    }
+
+    /// Start a scope that frees everything allocated in it once the returned guard is dropped.
+    pub fn scope(&mut self) -> ScopeGuard<'_, 'a> {
+        ScopeGuard::new(self)
+    }
+
+    /// Absorb an allocator whose memory starts right where the memory of this one ends,
+    /// so both become one bigger arena.
+    /// 
+    /// Chunks in use on either side stay valid, and the free chunks on both sides of the boundary are merged.
+    /// The guard region of this allocator (if any) becomes free memory and the one of `other` is kept.
+    /// The name, watermark and modes of this allocator are kept, the statistics of both are added up.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `other` - The allocator to be absorbed, its backing is kept alive by this one.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(())` - If the allocators were merged.
+    /// * `Err(VallocError)` - `NotAdjacent` if `other` doesn't start where this allocator ends,
+    ///   `Frozen` if either one is frozen, `UnexpectedHeader` if `other` was made with `new_in_place`
+    ///   and `PageSizeMismatch` if they don't use the same page size. Nothing is changed then.
+    pub fn merge_with(&mut self, other: Valloc<'a>) -> Result<(), VallocError> {
+        if self.frozen || other.frozen { return Err(VallocError::Frozen); }
+        if other.header > 0 { return Err(VallocError::UnexpectedHeader); }
+        if other.page != self.page {
+            return Err(VallocError::PageSizeMismatch { ours: self.page, theirs: other.page });
+        }
+        let boundary = self.memory.as_ptr_range().end as *mut u8;
+        if !std::ptr::eq(other.memory.as_ptr(), boundary) || other.memory.is_empty() {
+            return Err(VallocError::NotAdjacent { first: boundary as usize, second: other.memory.as_ptr() as usize });
+        }
+
+        // the old guard is just memory in the middle of the arena now
+        if self.guard > 0 {
+            let index = self.chunks.iter().position(|x| self.is_guard(x.ptr)).expect("The guard is always a chunk!");
+            let chunk = self.chunks.iter_mut().nth(index).expect("Chunk index out of bounds!");
+            chunk.in_use = false;
+            self.chunks.available += self.guard;
+            // merged like any freed chunk, the boundary below only joins it with the first chunk of `other`
+            self.chunks.merge_next(index);
+            self.chunks.merge_prev(index);
+        }
+
+        let len = self.memory.len();
+        let Valloc { memory, chunks, backing, counters, guard, frontier, .. } = other;
+        for mut chunk in chunks.list {
+            // ids are only unique within one allocator, an id of 0 still means never allocated
+            if chunk.allocation_id != 0 { chunk.allocation_id = self.counters.next_allocation_id(); }
+            self.chunks.push_back(chunk);
+        }
+        self.chunks.available += chunks.available;
+        self.counters.total_allocated += counters.total_allocated;
+        self.counters.total_freed += counters.total_freed;
+        self.counters.relocations += counters.relocations;
+        self.counters.merges_forward += counters.merges_forward;
+        self.counters.merges_backward += counters.merges_backward;
+        self.counters.frees_without_merge += counters.frees_without_merge;
+        self.counters.total_scanned += counters.total_scanned;
+        if self.deterministic { self.frontier = len + frontier; }
+        self.guard = guard;
+
+        let mut parts = std::mem::take(&mut self.backing).into_parts(len);
+        parts.extend(backing.into_parts(memory.len()));
+        self.backing = match parts.iter().all(|(_, x)| matches!(x, Backing::Borrowed)) {
+            true => Backing::Borrowed,
+            false => Backing::Merged(parts),
+        };
+        // both slices were valid for 'a and are right next to each other
+        self.memory = unsafe { std::slice::from_raw_parts(self.memory.as_ptr(), len + memory.len()) };
+        self.counters.peak_used = self.counters.peak_used.max(self.memory.len() - self.chunks.available);
+
+        // coalesce across the boundary
+        let before = self.chunks.iter().position(|x| !x.in_use && x.ptr.wrapping_add(x.size) == boundary);
+        let after = self.chunks.iter().position(|x| !x.in_use && x.ptr == boundary);
+        if let (Some(before), Some(after)) = (before, after) {
+            let size = self.chunks.discard(after);
+            let before = if after < before { before - 1 } else { before };
+            self.chunks.iter_mut().nth(before).expect("Chunk index out of bounds!").size += size;
+        }
+        self.sync_header();
+        Ok(())
+    }
+
+    /// Create a new Valloc instance whose last `guard_bytes` bytes are never handed out.
+    /// 
+    /// The guard is zeroed and kept as a chunk in use that can't be freed, moved or reallocated,
+    /// so something that writes past the end of the last allocation lands in the guard
+    /// and is reported by `validate_all`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `memory` - The existing memory to be used by the allocator.
+    /// * `guard_bytes` - The size of the guard, must be smaller than the memory.
+    /// 
+    /// # Panics
+    /// 
+    /// Panics if the guard doesn't leave any memory to allocate from.
+    pub fn with_guard_region(memory: &'a mut [u8], guard_bytes: usize) -> Self {
+        assert!(guard_bytes < memory.len(), "Guard region must be smaller than the memory! Guard: {guard_bytes}, Memory: {}", memory.len());
+
+        let len = memory.len() - guard_bytes;
+        memory[len..].fill(0);
+        let mut valloc = Valloc::new(memory);
+        if guard_bytes > 0 {
+            let chunk = valloc.chunks.iter_mut().next().expect("A new allocator has one chunk!");
+            chunk.size = len;
+            let guard = Box::new(ChunkNode::new(chunk.ptr.wrapping_add(len), guard_bytes, true));
+            valloc.chunks.push_back(guard);
+            valloc.chunks.available = len;
+            valloc.guard = guard_bytes;
+        }
+        valloc
+    }
+
+    /// Create a new Valloc instance that hands out whole pages.
+    /// 
+    /// Every allocation starts on a multiple of `page` and its size is rounded up to a multiple of `page`,
+    /// like memory that comes from `mmap`. This wastes the rest of the last page of every allocation.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `memory` - The existing memory to be used by the allocator.
+    /// * `page` - The size of a page, must be a power of two.
+    pub fn with_page_size(memory: &'a mut [u8], page: usize) -> Self {
+        assert!(page.is_power_of_two(), "Page size must be a power of two => Requested: {page}");
+
+        let mut valloc = Valloc::new(memory);
+        valloc.page = page;
+        valloc
+    }
+
+    /// The size of a page as given to `with_page_size`, 1 for an allocator with byte granularity.
+    pub fn page_size(&self) -> usize {
+        self.page
+    }
+
+    /// Allocate `n` whole pages of `page_size()` bytes, starting at an address that is a multiple of the page size.
+    /// 
+    /// Freeing the region gives all of its pages back.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<[u8]>)` - The `n * page_size()` bytes if successful.
+    /// * `Err(VallocError)` - The reason the allocation failed.
+    pub fn alloc_pages(&mut self, n: usize) -> Result<SmartPointer<[u8]>, VallocError> {
+        let size = n.checked_mul(self.page).ok_or(VallocError::CapacityOverflow)?;
+        let ptr = alloc_aligned::<u8>(self, size, self.page)?;
+        Ok(SmartPointer::new(NonNull::slice_from_raw_parts(ptr.non_null_ptr(), size)))
+    }
+
+    // the size of the chunk that is actually reserved for `size` bytes
+    fn page_rounded(&self, size: usize) -> Option<usize> {
+        size.checked_next_multiple_of(self.page)
+    }
+
+    // the guard chunk (if there is one) starts here
+    fn is_guard(&self, ptr: *mut u8) -> bool {
+        self.guard > 0 && ptr as usize == self.memory.as_ptr() as usize + self.memory.len() - self.guard
+    }
+
+    // the guard or the header, chunks in use that were never handed out and can't be freed, moved or resized
+    fn is_reserved(&self, ptr: *mut u8) -> bool {
+        self.is_guard(ptr) || (self.header > 0 && std::ptr::eq(ptr, self.memory.as_ptr()))
+    }
+
+    // the offset right after the last chunk in use, not counting the guard
+    fn live_end(&self) -> usize {
+        let base = self.memory.as_ptr() as usize;
+        self.chunks.iter()
+            .filter(|x| x.in_use && !self.is_guard(x.ptr))
+            .map(|x| x.ptr as usize + x.size - base)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // the padding needed at the start of the free `chunk` to place an allocation aligned to `align` in it
+    // in deterministic mode nothing before the frontier can be used
+    fn padding_in(&self, chunk: &ChunkNode, align: usize) -> Option<usize> {
+        let start = if self.deterministic {
+            let frontier = self.memory.as_ptr() as usize + self.frontier;
+            if chunk.ptr as usize + chunk.size <= frontier { return None; }
+            frontier.max(chunk.ptr as usize)
+        } else { chunk.ptr as usize };
+        Some(start - chunk.ptr as usize + Valloc::align_offset(start, align))
+    }
+
+    // frees the old chunk of an allocation that was moved to `new`, which keeps the age of the old chunk
+    fn relocated<T: ?Sized, U: ?Sized>(&mut self, old: SmartPointer<T>, new: &SmartPointer<U>) -> Result<(), VallocError> {
+        let origin = self.find_chunk(&old).ok_or(VallocError::NotAllocated { addr: old.as_ptr() as *mut u8 as usize })?;
+        let (allocation_id, tag) = (origin.allocation_id, origin.tag);
+        #[cfg(feature = "backtrace")]
+        let backtrace = origin.backtrace.clone();
+        if let Some(chunk) = self.chunks.iter_mut().find(|x| x.get_ptr() == new.as_ptr() as *mut u8) {
+            chunk.allocation_id = allocation_id;
+            chunk.tag = tag;
+            #[cfg(feature = "backtrace")]
+            { chunk.backtrace = backtrace; }
+        }
+
+        free(self, old)?;
+        self.counters.relocations += 1;
+        Ok(())
+    }
+
+    // cuts the chunk at `index` down to `new_size` and frees the rest
+    // returns the number of bytes freed
+    fn shrink_chunk(&mut self, index: usize, new_size: usize) -> usize {
+        let chunk = self.chunks.iter_mut().nth(index).expect("Chunk index out of bounds!");
+        let freed = chunk.size - new_size;
+        if freed == 0 { return 0; }
+
+        chunk.size = new_size;
+        let tail = ChunkNode::new(chunk.ptr.wrapping_add(new_size), freed, false);
+        self.chunks.insert_new(index + 1, tail);
+
+        self.chunks.available += freed;
+        self.counters.total_freed += freed;
+        if self.coalesce_on_free {
+            self.chunks.merge_next(index + 1);
+        }
+        self.sync_header();
+        freed
+    }
+
+    fn note_alloc(&mut self, size: usize) {
+        self.counters.total_allocated += size;
+        self.counters.peak_used = self.counters.peak_used.max(self.memory.len() - self.chunks.available);
+
+        let available = self.chunks.available;
+        if let Some(watermark) = self.watermark.as_mut() {
+            if available >= watermark.bytes {
+                watermark.below = false;
+            } else if !watermark.below {
+                watermark.below = true;
+                (watermark.callback.borrow_mut())(available);
+            }
+        }
+    }
+
+    fn from_parts(memory: &'a [u8], chunks: ChunkList) -> Self {
+        Self { 
+            memory, chunks, 
+            coalesce_on_free: true, 
+            auto_compact: None,
+            backing: Backing::Borrowed, 
+            counters: Counters::default(),
+            recent_frees: RecentFrees::default(),
+            name: None,
+            watermark: None,
+            observer: None,
+            guard: 0,
+            header: 0,
+            deterministic: false,
+            frontier: 0,
+            page: 1,
+            frozen: false,
+            zero_on_alloc: false,
+            #[cfg(test)]
+            last_realloc_copied: 0,
+        }
+    }
+}
+
+impl Valloc<'static> {
+    /// Rebuild an allocator over `mem` from a chunk list made with `Valloc::chunk_list_repr`.
+    /// 
+    /// The chunk list is not trusted, every chunk has to lie inside of the memory
+    /// and together they have to cover it without gaps or overlaps.
+    /// Free chunks next to each other are fine, they just weren't merged yet.
+    /// 
+    /// # Safety
+    /// 
+    /// `mem` must be valid for reads and writes of `len` bytes for as long as the allocator is used,
+    /// and the chunks marked in use must not be handed out to anyone else.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(Valloc)` - The rebuilt allocator if the chunk list is valid.
+    /// * `Err(VallocError)` - The first problem found with the parts.
+    pub unsafe fn try_from_raw_parts(mem: *mut u8, len: usize, chunks: ChunkListRepr) -> Result<Valloc<'static>, VallocError> {
+        let memory = NonNull::new(mem).ok_or(VallocError::OutOfBounds { addr: 0 })?;
+        if len == 0 { return Err(VallocError::ZeroSize); }
+
+        let mut valloc = Valloc::from_mem(memory, len);
+        valloc.replace_chunks(&chunks)?;
+        Ok(valloc)
+    }
+}
+
+impl Valloc<'static> {
+    /// Create a new Valloc instance over a buffer that lives for the whole program,
+    /// like a `static mut BUF: [u8; N]` on an embedded target.
+    /// 
+    /// This is the same as `new` with the lifetime pinned to `'static`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `buf` - The buffer to be managed by the allocator.
+    pub fn new_from_static(buf: &'static mut [u8]) -> Self {
+        Valloc::new(buf)
+    }
+
+    /// Create a new Valloc instance that owns a copy of `data`, e.g. a blob loaded from a file.
+    /// 
+    /// The whole copy starts out as a single chunk in use, so it is treated as live data
+    /// and not handed out again until it is freed.
+    /// An empty `data` gives an allocator without any memory, like `Valloc::default`.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use valloc::allocator::Valloc;
+    /// let allocator = Valloc::from_slice_copy(&[1, 2, 3, 4]);
+    /// assert_eq!(allocator.read_at_offset::<u8>(2), Ok(3));
+    /// ```
+    pub fn from_slice_copy(data: &[u8]) -> Self {
+        if data.is_empty() { return Valloc::default(); }
+
+        let buffer = std::sync::Arc::new(HeapBuffer::copy_of(data));
+        let mut valloc = Valloc::from_mem(NonNull::new(buffer.as_ptr()).expect("Box returned null!"), buffer.len());
+        valloc.backing = Backing::Heap(buffer);
+
+        let allocation_id = valloc.counters.next_allocation_id();
+        let chunk = valloc.chunks.iter_mut().next().expect("A new allocator has one chunk!");
+        chunk.in_use = true;
+        chunk.allocation_id = allocation_id;
+        valloc.chunks.available = 0;
+        valloc.note_alloc(data.len());
+        valloc
+    }
+}
+
+#[cfg(all(unix, feature = "mmap"))]
+impl Valloc<'static> {
+    /// Create a new Valloc instance over an anonymous `mmap` of `len` bytes.
+    /// 
+    /// Only the pages that are actually touched get committed by the OS,
+    /// which makes this a good fit for very large arenas.
+    /// The mapping is released when the allocator is dropped.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `len` - The size of the arena, in bytes.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use valloc::allocator::Valloc;
+    /// let allocator = Valloc::with_backing_from_mmap(1 << 30).unwrap();
+    /// ```
+    pub fn with_backing_from_mmap(len: usize) -> Result<Self, VallocError> {
+        let region = std::sync::Arc::new(crate::mmap::MmapRegion::new(len)?);
+        let mut valloc = Valloc::from_mem(NonNull::new(region.as_ptr()).expect("mmap returned null!"), len);
+        valloc.backing = Backing::Mmap(region);
+        Ok(valloc)
+    }
+}
+
+impl Valloc<'_> {
+    /// The number of padding bytes needed to move `addr` up to a multiple of `align`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `addr` - The address (or offset) to be aligned.
+    /// * `align` - The alignment, must be a power of two.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use valloc::allocator::Valloc;
+    /// assert_eq!(Valloc::align_offset(13, 8), 3);
+    /// assert_eq!(Valloc::align_offset(16, 8), 0);
+    /// ```
+    pub fn align_offset(addr: usize, align: usize) -> usize {
+        debug_assert!(align.is_power_of_two(), "Alignment must be a power of two => Requested: {align}");
+        addr.wrapping_neg() & (align - 1)
+    }
+
+    pub fn from_mem(
+        memory: NonNull<u8>, len: usize
+    ) -> Self {
+        debug_assert!(len > 0, "Memory length must be greater than 0!");
+
+        let chunks = ChunkList::new(
+            Some(
+                Box::new(ChunkNode::new(
+                    memory.as_ptr(), 
+                    len, 
+                    false
+                ))
+            ),
+            len
+        );
+        Self::from_parts(unsafe{std::slice::from_raw_parts_mut(memory.as_ptr(), len)}, chunks)
+    }
+
+    pub fn chunks(&self) -> &ChunkList {
+        &self.chunks
+    }
+
+    /// The number of free bytes left, not necessarily in one piece.
+    pub fn available(&self) -> usize {
+        self.chunks.available
+    }
+
+    /// Like `available`, but without the padding every free chunk needs at its start
+    /// before an allocation aligned to `align` can be placed in it.
+    /// 
+    /// This is an upper bound for the total size of allocations aligned to `align` that can still succeed.
+    pub fn available_aligned(&self, align: usize) -> usize {
+        debug_assert!(align.is_power_of_two(), "Alignment must be a power of two => Requested: {align}");
+        self.chunks.iter()
+            .filter(|x| !x.in_use)
+            .filter_map(|x| self.padding_in(x, align).map(|padding| x.size.saturating_sub(padding)))
+            .sum()
+    }
+
+    /// Checks if nothing is allocated, the guard region and the header of `new_in_place` (if any) don't count.
+    pub fn is_empty(&self) -> bool {
+        !self.chunks.iter().any(|x| x.in_use && !self.is_reserved(x.ptr))
+    }
+
+    /// Checks if there is no free memory left at all.
+    pub fn is_full(&self) -> bool {
+        self.chunks.available == 0
+    }
+
+    /// Direct access to the chunk list.
+    /// 
+    /// Changing the chunks by hand can easily corrupt the allocator,
+    /// this is meant for tooling and for testing the allocator's own checks.
+    pub fn chunks_mut(&mut self) -> &mut ChunkList {
+        &mut self.chunks
+    }
+
+    /// Panics if the region `[ptr..ptr + size]` overlaps any chunk in use other than its own.
+    /// 
+    /// `alloc` runs this on every allocation in debug builds
+    /// so a corrupted chunk list is caught before two allocations alias.
+    pub fn assert_no_overlap(&self, ptr: *mut u8, size: usize) {
+        let base = self.memory.as_ptr() as usize;
+        let (start, end) = (ptr as usize, ptr as usize + size);
+
+        let mut own = false;
+        for chunk in self.chunks.iter().filter(|x| x.in_use) {
+            let (cstart, cend) = (chunk.ptr as usize, chunk.ptr as usize + chunk.size);
+            // skip the chunk the region was allocated from
+            if !own && cstart == start && cend == end {
+                own = true;
+                continue;
+            }
+            if cstart < end && start < cend {
+                panic!(
+                    "Allocation [{}..{}] overlaps a chunk in use [{}..{}]!",
+                    start - base, end - base, cstart - base, cend - base
+                );
+            }
+        }
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        self.memory
+    }
+
+    /// A read-only view of every byte managed by the allocator,
+    /// free or not, e.g. for hashing or serializing the whole arena.
+    pub fn backing(&self) -> &[u8] {
+        self.memory
+    }
+
+    /// Tries to make the chunk in use at `ptr` at least `new_size` bytes without moving it.
+    /// 
+    /// This succeeds if the chunk is already big enough or if the chunk after it is free and big enough to grow into.
+    /// 
+    /// # Returns
+    /// 
+    /// * `true` - If the chunk is now at least `new_size` bytes
+    /// * `false` - If nothing was changed, always while the allocator is frozen
+    pub fn grow_in_place<T: ?Sized>(&mut self, ptr: &SmartPointer<T>, new_size: usize) -> bool {
+        if self.frozen { return false; }
+        let Ok(index) = self.live_index(ptr.as_ptr() as *mut u8) else { return false; };
+        let size = self.chunks.iter().nth(index).expect("Chunk index out of bounds!").size;
+
+        if new_size <= size { return true; }
+        let Some(new_size) = self.page_rounded(new_size) else { return false; };
+        // the free space after any chunk but the last one is behind the frontier and can't be reused
+        let end = ptr.as_ptr() as *mut u8 as usize + size - self.memory.as_ptr() as usize;
+        if self.deterministic && end != self.frontier { return false; }
+        if !self.chunks.grow_into_next(index, new_size - size) { return false; }
+
+        self.chunks.available -= new_size - size;
+        self.note_alloc(new_size - size);
+        if self.deterministic { self.frontier += new_size - size; }
+        self.sync_header();
+        true
+    }
+
+    /// Cuts the chunk in use at `ptr` down to `new_size` bytes (rounded up to the page size), freeing the rest.
+    /// 
+    /// The allocation never moves, so `ptr` stays valid.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(usize)` - The number of bytes that went back to the free list, 0 if the chunk was already small enough
+    /// * `Err(VallocError)` - If the allocator is frozen, `ptr` is not the start of a chunk in use, or `new_size` is 0
+    pub fn shrink_in_place<T: ?Sized>(&mut self, ptr: &SmartPointer<T>, new_size: usize) -> Result<usize, VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        let addr = ptr.as_ptr() as *mut u8;
+        let index = self.live_index(addr)?;
+        if new_size == 0 { return Err(VallocError::ZeroSize); }
+        let size = self.chunks.iter().nth(index).expect("Chunk index out of bounds!").size;
+
+        let keep = self.page_rounded(new_size).expect("A smaller size can't overflow!").min(size);
+        // the frontier only moves back if nothing was allocated after this chunk
+        let end = addr as usize + size - self.memory.as_ptr() as usize;
+        let freed = self.shrink_chunk(index, keep);
+        if self.deterministic && end == self.frontier { self.frontier -= freed; }
+        Ok(freed)
+    }
+
+    /// The number of contiguous free bytes from `offset` (a byte offset into the memory) onwards.
+    /// 
+    /// Adjacent free chunks count as one run even if they have not been merged yet.
+    /// 
+    /// # Returns
+    /// 
+    /// * `usize` - The length of the free run, or 0 if `offset` is in use or out of range
+    pub fn free_run_at(&self, offset: usize) -> usize {
+        let addr = self.memory.as_ptr() as usize + offset;
+        let free: Vec<&ChunkNode> = self.chunks.iter()
+            .map(|x| x.as_ref())
+            .filter(|x| !x.in_use)
+            .collect();
+
+        let Some(start) = free.iter()
+            .position(|x| (x.ptr as usize..x.ptr as usize + x.size).contains(&addr)) else { return 0; };
+
+        let mut end = free[start].ptr as usize + free[start].size;
+        for chunk in &free[start + 1..] {
+            if chunk.ptr as usize != end { break; }
+            end += chunk.size;
+        }
+        end - addr
+    }
+
+    /// Finds the live chunk that was allocated the longest time ago.
+    /// 
+    /// Chunks moved by `realloc` keep their age.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Some((*mut u8, usize))` - The pointer to and size of the oldest live chunk.
+    /// * `None` - If nothing is allocated.
+    pub fn oldest_live(&self) -> Option<(*mut u8, usize)> {
+        self.chunks.iter()
+            .filter(|x| x.in_use && !self.is_reserved(x.ptr))
+            .min_by_key(|x| x.allocation_id)
+            .map(|x| (x.ptr, x.size))
+    }
+
+    /// A 64 bit FNV-1a hash of the contents of every chunk in use, in address order.
+    /// 
+    /// Free memory is not part of the hash, so comparing two checksums taken at different times
+    /// shows if any live allocation was changed in between.
+    pub fn checksum(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        self.chunks.iter()
+            .filter(|x| x.in_use && !self.is_reserved(x.ptr))
+            .flat_map(|x| unsafe { std::slice::from_raw_parts(x.ptr as *const u8, x.size) })
+            .fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+    }
+
+    /// Lists every chunk that is still in use, oldest first.
+    /// 
+    /// Call this when everything should have been freed, whatever is left over was leaked.
+    /// With the `backtrace` feature every leak also carries the stack it was allocated from.
+    pub fn leak_check(&self) -> Vec<Leak> {
+        let mut live: Vec<&ChunkNode> = self.chunks.iter()
+            .map(|x| x.as_ref())
+            .filter(|x| x.in_use && !self.is_reserved(x.ptr))
+            .collect();
+        live.sort_by_key(|x| x.allocation_id);
+        live.into_iter()
+            .map(|x| Leak {
+                ptr: x.ptr,
+                size: x.size,
+                #[cfg(feature = "backtrace")]
+                backtrace: x.backtrace.clone().map(|mut backtrace| { backtrace.resolve(); backtrace }),
+            })
+            .collect()
+    }
+
+    /// Counts the chunks in use by size, index `i` holds the ones with a size in `[2^i, 2^(i+1))`.
+    /// 
+    /// Anything of 2^31 bytes or more ends up in the last bucket.
+    pub fn size_histogram(&self) -> [usize; 32] {
+        let mut histogram = [0; 32];
+        for chunk in self.chunks.iter().filter(|x| x.in_use && x.size > 0 && !self.is_reserved(x.ptr)) {
+            histogram[(chunk.size.ilog2() as usize).min(31)] += 1;
+        }
+        histogram
+    }
+
+    /// Every live allocation as `(ptr, size, tag)`, in address order.
+    /// 
+    /// Allocations that weren't made with `alloc_tagged` have no tag.
+    pub fn live_tagged(&self) -> impl Iterator<Item = (*mut u8, usize, Option<&'static str>)> {
+        self.chunks.iter()
+            .filter(|x| x.in_use && !self.is_reserved(x.ptr))
+            .map(|x| (x.ptr, x.size, x.tag))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// How often `free` managed to merge the freed chunk with its neighbours.
+    pub fn coalesce_stats(&self) -> CoalesceStats {
+        CoalesceStats {
+            merges_forward: self.counters.merges_forward,
+            merges_backward: self.counters.merges_backward,
+            frees_without_merge: self.counters.frees_without_merge,
+        }
+    }
+
+    /// The number of allocations made over the lifetime of the allocator,
+    /// which is also the allocation id of the newest one.
+    pub fn allocation_count(&self) -> u64 {
+        self.counters.allocations
+    }
+
+    /// How many bytes the most recent `realloc` copied, 0 if it resized in place.
+    #[cfg(test)]
+    pub fn last_realloc_copied_bytes(&self) -> usize {
+        self.last_realloc_copied
+    }
+
+    /// The number of chunks the most recent `alloc` had to look at before it found one that fits
+    /// (or all of them if none did).
+    pub fn last_search_len(&self) -> usize {
+        self.counters.last_search_len
+    }
+
+    /// The number of chunks looked at by every `alloc` so far, see `last_search_len`.
+    pub fn total_scanned(&self) -> usize {
+        self.counters.total_scanned
+    }
+
+    /// The number of times `realloc` had to move a chunk because it could not grow it in place.
+    pub fn relocations(&self) -> usize {
+        self.counters.relocations
+    }
+
+    /// Takes a snapshot of the current state of the heap.
+    /// 
+    /// Two snapshots can be compared with `HeapStats::delta`
+    /// to see what happened in between.
+    pub fn stats(&self) -> HeapStats {
+        let (mut live_chunks, mut free_chunks, mut largest_free) = (0, 0, 0);
+        for chunk in self.chunks.iter().filter(|x| !self.is_reserved(x.ptr)) {
+            if chunk.in_use {
+                live_chunks += 1;
+            } else {
+                free_chunks += 1;
+                largest_free = largest_free.max(chunk.size);
+            }
+        }
+
+        HeapStats {
+            capacity: self.memory.len(),
+            used: self.memory.len() - self.chunks.available,
+            available: self.chunks.available,
+            live_chunks,
+            free_chunks,
+            largest_free,
+            total_allocated: self.counters.total_allocated,
+            total_freed: self.counters.total_freed,
+            peak_used: self.counters.peak_used,
+        }
+    }
+
+    /// The chunk list as plain data, in address order, to be given back to `Valloc::try_from_raw_parts`.
+    pub fn chunk_list_repr(&self) -> ChunkListRepr {
+        let base = self.memory.as_ptr() as usize;
+        let chunks = self.chunks.iter()
+            .map(|x| ChunkRepr { offset: x.ptr as usize - base, size: x.size, in_use: x.in_use })
+            .collect();
+        ChunkListRepr { chunks }
+    }
+
+    // rewrites the header of an allocator made with `new_in_place` after the chunk list changed
+    // the count is always the real one, so a list that outgrew the header is never read back cut short
+    fn sync_header(&mut self) {
+        if self.header == 0 { return; }
+        let capacity = (self.header - HEADER_PREFIX) / HEADER_ENTRY;
+        let base = self.memory.as_ptr() as *mut u8;
+
+        let count = (self.chunks.list.len() as u64).to_le_bytes();
+        unsafe { base.wrapping_add(16).cast::<[u8; 8]>().write_unaligned(count); }
+        for (i, chunk) in self.chunks.iter().take(capacity).enumerate() {
+            let entry = [chunk.ptr as usize - base as usize, chunk.size, chunk.in_use as usize].map(|x| (x as u64).to_le_bytes());
+            unsafe { base.wrapping_add(HEADER_PREFIX + i * HEADER_ENTRY).cast::<[[u8; 8]; 3]>().write_unaligned(entry); }
+        }
+    }
+
+    // the number of chunks the header at the front of the memory has room for
+    fn header_capacity(&self) -> Result<usize, VallocError> {
+        let magic = self.read_at_offset(0).map(u64::from_le_bytes);
+        let capacity = self.read_at_offset(8).map(u64::from_le_bytes).map_err(|_| VallocError::MissingHeader)? as usize;
+        let fits = capacity.checked_mul(HEADER_ENTRY).and_then(|x| x.checked_add(HEADER_PREFIX)).is_some_and(|x| x <= self.memory.len());
+        if magic != Ok(HEADER_MAGIC) || !fits { return Err(VallocError::MissingHeader); }
+        Ok(capacity)
+    }
+
+    /// Copies the whole memory and the chunk list, to be compared against later with `diff_since`.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { memory: self.memory.to_vec(), chunks: self.chunk_list_repr() }
+    }
+
+    /// The changes made since `prev` was taken, small enough to store or send instead of a new snapshot.
+    /// 
+    /// The memory is compared in blocks of `DIFF_BLOCK` bytes and every run of changed blocks
+    /// becomes one range, so the diff can hold some bytes that didn't actually change.
+    pub fn diff_since(&self, prev: &Snapshot) -> SnapshotDiff {
+        let mut ranges: Vec<(usize, Vec<u8>)> = Vec::new();
+        for (index, block) in self.memory.chunks(DIFF_BLOCK).enumerate() {
+            let offset = index * DIFF_BLOCK;
+            if prev.memory.get(offset..offset + block.len()) == Some(block) { continue; }
+            match ranges.last_mut() {
+                // glue runs of changed blocks together
+                Some((start, bytes)) if *start + bytes.len() == offset => bytes.extend_from_slice(block),
+                _ => ranges.push((offset, block.to_vec())),
+            }
+        }
+
+        let chunks = Some(self.chunk_list_repr()).filter(|x| *x != prev.chunks);
+        SnapshotDiff { ranges, chunks }
+    }
+
+    /// Replays a diff made with `diff_since`, which brings this allocator to the state the diff was made from
+    /// as long as it was in the state of the snapshot before.
+    /// 
+    /// Every chunk in use gets a new allocation id if the chunk list is replaced.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(())` - If the diff was applied.
+    /// * `Err(VallocError)` - If the diff doesn't fit this allocator, nothing was changed.
+    pub fn apply_diff(&mut self, diff: &SnapshotDiff) -> Result<(), VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        let targets = diff.ranges.iter()
+            .map(|(offset, bytes)| self.offset_ptr(*offset, bytes.len()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(chunks) = &diff.chunks {
+            self.replace_chunks(chunks)?;
+        }
+
+        for (target, (_, bytes)) in targets.into_iter().zip(&diff.ranges) {
+            unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), target, bytes.len()); }
+        }
+        Ok(())
+    }
+
+    // swaps in a chunk list built from `repr`, the old one is kept if the new one doesn't describe a valid heap
+    // free chunks next to each other are fine, they just weren't merged yet
+    fn replace_chunks(&mut self, repr: &ChunkListRepr) -> Result<(), VallocError> {
+        let (mem, len) = (self.memory.as_ptr() as *mut u8, self.memory.len());
+        let mut list = ChunkList::new(None, 0);
+        // the list has to be in address order, whatever order the chunks were given in
+        let mut chunks: Vec<&ChunkRepr> = repr.chunks.iter().collect();
+        chunks.sort_by_key(|x| x.offset);
+        for chunk in chunks {
+            if chunk.offset.checked_add(chunk.size).is_none_or(|end| end > len) {
+                return Err(VallocError::OutOfBounds { addr: (mem as usize).wrapping_add(chunk.offset) });
+            }
+            let mut node = ChunkNode::from_offset(mem, chunk.offset, chunk.size, chunk.in_use);
+            if chunk.in_use {
+                node.allocation_id = self.counters.next_allocation_id();
+            } else {
+                list.available += chunk.size;
+            }
+            list.push_back(Box::new(node));
+        }
+
+        let old = std::mem::replace(&mut self.chunks, list);
+        match self.validate_all().into_iter().find(|x| !matches!(x, ConsistencyIssue::AdjacentFree { .. })) {
+            Some(issue) => {
+                self.chunks = old;
+                Err(VallocError::Inconsistent { issue })
+            },
+            None => {
+                self.sync_header();
+                Ok(())
+            },
+        }
+    }
+
+    /// Checks the chunk list against the memory it manages and returns every problem it finds.
+    /// 
+    /// The chunks must cover the memory exactly, without gaps or overlaps,
+    /// and the tracked available size must match the free chunks.
+    /// Free chunks next to each other are only reported when `coalesce_on_free` is on.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Vec<ConsistencyIssue>` - Every broken invariant, empty if the heap is consistent.
+    pub fn validate_all(&self) -> Vec<ConsistencyIssue> {
+        let base = self.memory.as_ptr() as usize;
+        let mut issues = Vec::new();
+        let mut cursor = 0;
+        let mut previous: Option<&ChunkNode> = None;
+        for chunk in self.chunks.iter().map(|x| x.as_ref()) {
+            // a corrupted chunk can point anywhere, it is reported and left out of the rest of the checks
+            let start = (chunk.ptr as usize).wrapping_sub(base);
+            let Some(end) = start.checked_add(chunk.size).filter(|&end| end <= self.memory.len()) else {
+                issues.push(ConsistencyIssue::OutOfRange { at: start, size: chunk.size });
+                continue;
+            };
+            if let Some(previous) = previous {
+                let at = previous.ptr as usize - base;
+                if start < cursor {
+                    issues.push(ConsistencyIssue::Overlap { a: at, b: start });
+                } else if start == cursor && self.coalesce_on_free && !previous.in_use && !chunk.in_use {
+                    issues.push(ConsistencyIssue::AdjacentFree { a: at, b: start });
+                }
+            }
+            if start > cursor {
+                issues.push(ConsistencyIssue::Gap { at: cursor, size: start - cursor });
+            }
+            cursor = cursor.max(end);
+            previous = Some(chunk);
+        }
+        if cursor < self.memory.len() {
+            issues.push(ConsistencyIssue::Gap { at: cursor, size: self.memory.len() - cursor });
+        }
+
+        // anything written into the guard came from an overrun
+        let guard_start = self.memory.len() - self.guard;
+        if let Some(i) = self.memory[guard_start..].iter().position(|&x| x != 0) {
+            issues.push(ConsistencyIssue::GuardOverwritten { at: guard_start + i });
+        }
+
+        let actual = self.chunks.iter().filter(|x| !x.in_use).fold(0usize, |sum, x| sum.saturating_add(x.size));
+        if actual != self.chunks.available {
+            issues.push(ConsistencyIssue::AvailableMismatch { tracked: self.chunks.available, actual });
+        }
+
+        let capacity = self.header.saturating_sub(HEADER_PREFIX) / HEADER_ENTRY;
+        if self.header > 0 && self.chunks.list.len() > capacity {
+            issues.push(ConsistencyIssue::HeaderFull { chunks: self.chunks.list.len(), capacity });
+        }
+        issues
+    }
+
+    /// A cheap check of the chunk list, for asserting in hot loops where `validate_all` is too slow.
+    /// 
+    /// Only checks that no chunk is empty and that the tracked available size matches the free chunks.
+    /// This doesn't allocate.
+    pub fn quick_check(&self) -> bool {
+        let mut free = 0;
+        for chunk in self.chunks.iter() {
+            if chunk.size == 0 { return false; }
+            if !chunk.in_use { free += chunk.size; }
+        }
+        free == self.chunks.available
+    }
+
+    /// Same as `validate_all` but panics with every issue found.
+    pub fn assert_invariants(&self) {
+        let issues = self.validate_all();
+        if !issues.is_empty() {
+            panic!("Heap is inconsistent! {issues:?}");
+        }
+    }
+
+    /// The distance in bytes from `a` to `b`, negative if `b` comes first.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(isize)` - The address of `b` minus the address of `a`.
+    /// * `Err(VallocError)` - If either pointer is outside of the memory of the allocator.
+    pub fn bytes_between<T: ?Sized, U: ?Sized>(&self, a: &SmartPointer<T>, b: &SmartPointer<U>) -> Result<isize, VallocError> {
+        let range = self.memory.as_ptr_range();
+        let offset = |addr: *mut u8| {
+            if range.contains(&(addr as *const u8)) { Ok(addr as usize - range.start as usize) }
+            else { Err(VallocError::OutOfBounds { addr: addr as usize }) }
+        };
+        let (a, b) = (offset(a.as_ptr() as *mut u8)?, offset(b.as_ptr() as *mut u8)?);
+        Ok(b as isize - a as isize)
+    }
+
+    /// Finds the chunk starting at the given pointer, whether it is in use or not.
+    pub fn find_chunk<T: ?Sized>(&self, ptr: &SmartPointer<T>) -> Option<&ChunkNode> {
+        self.chunks.iter()
+            .map(|x| x.as_ref())
+            .find(|x| x.get_ptr() == ptr.as_ptr() as *mut u8)
+    }
+
+    /// The chunk covering the byte at `offset` (from the start of the memory), free or not.
+    /// 
+    /// This is `find_chunk` for offsets, and unlike it the offset doesn't have to be the start of the chunk.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Some(Span)` - The chunk the byte belongs to.
+    /// * `None` - If `offset` is past the end of the memory.
+    pub fn chunk_at_offset(&self, offset: usize) -> Option<Span> {
+        let base = self.memory.as_ptr() as usize;
+        self.chunks.iter()
+            .map(|x| Span { offset: x.ptr as usize - base, size: x.size, in_use: x.in_use })
+            .find(|x| x.offset <= offset && offset < x.offset + x.size)
+    }
+
+    /// The number of bytes that can actually be used at `ptr`, like `malloc_usable_size`.
+    /// 
+    /// This is the size of the whole chunk, which can be more than was asked for,
+    /// e.g. after `realloc` shrank it in place or over-allocated it to make room to grow.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(usize)` - The size of the chunk in use at `ptr`.
+    /// * `Err(VallocError)` - If `ptr` is not the start of a chunk in use.
+    pub fn usable_size<T: ?Sized>(&self, ptr: &SmartPointer<T>) -> Result<usize, VallocError> {
+        match self.find_chunk(ptr) {
+            Some(chunk) if self.owns(ptr) => Ok(chunk.size),
+            _ => Err(VallocError::NotAllocated { addr: ptr.as_ptr() as *mut u8 as usize }),
+        }
+    }
+
+    /// Checks if the pointer is the start of a chunk currently in use by this allocator.
+    pub fn owns<T: ?Sized>(&self, ptr: &SmartPointer<T>) -> bool {
+        self.find_chunk(ptr).is_some_and(|x| x.in_use && !self.is_reserved(x.ptr))
+    }
+
+    /// Split a live chunk into two live chunks that can be freed on their own.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ptr` - The start of the chunk to be split.
+    /// * `at` - The offset of the second chunk, in bytes. Must be inside of the chunk.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok((SmartPointer<u8>, SmartPointer<u8>))` - The first `at` bytes and the rest of the chunk.
+    /// * `Err(VallocError)` - The reason the chunk could not be split.
+    pub fn split_chunk<T: ?Sized>(&mut self, ptr: SmartPointer<T>, at: usize) -> Result<(SmartPointer<u8>, SmartPointer<u8>), VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        let index = self.live_index(ptr.as_ptr() as *mut u8)?;
+        let size = self.chunks.iter().nth(index).expect("Chunk index out of bounds!").size;
+        if at == 0 || at >= size {
+            return Err(VallocError::InvalidSplit { at, size });
+        }
+
+        // the id is only taken once the split can't fail anymore
+        let allocation_id = self.counters.next_allocation_id();
+        let chunk = self.chunks.iter_mut().nth(index).expect("Chunk index out of bounds!");
+        // both halves stay in use so the available size doesn't change
+        let mut second = ChunkNode::new(chunk.ptr.wrapping_add(at), chunk.size - at, true);
+        second.allocation_id = allocation_id;
+        second.tag = chunk.tag;
+        chunk.size = at;
+        let pointers = (chunk.smart_pointer(), second.smart_pointer());
+        self.chunks.insert_new(index + 1, second);
+        self.sync_header();
+        Ok(pointers)
+    }
+
+    /// Join two live chunks that are next to each other in memory into one, the inverse of `split_chunk`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `a` - The start of the first chunk.
+    /// * `b` - The start of the second chunk, which must begin right where `a` ends.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<u8>)` - The start of the joined chunk (the same address as `a`).
+    /// * `Err(VallocError)` - The reason the chunks could not be joined.
+    pub fn join_chunks(&mut self, a: SmartPointer<u8>, b: SmartPointer<u8>) -> Result<SmartPointer<u8>, VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        let (first, second) = (self.live_index(a.as_ptr())?, self.live_index(b.as_ptr())?);
+        let end = self.chunks.iter().nth(first).map(|x| x.ptr.wrapping_add(x.size));
+        if end != Some(b.as_ptr()) {
+            return Err(VallocError::NotAdjacent { first: a.as_ptr() as usize, second: b.as_ptr() as usize });
+        }
+
+        // the joined chunk keeps the age of the first one
+        let size = self.chunks.discard(second);
+        let first = if second < first { first - 1 } else { first };
+        let chunk = self.chunks.iter_mut().nth(first).expect("Chunk index out of bounds!");
+        chunk.size += size;
+        let joined = chunk.smart_pointer();
+        self.sync_header();
+        Ok(joined)
+    }
+
+    /// Move a single live chunk to `to_offset` in the memory, copying its contents along.
+    /// 
+    /// The destination must be free, apart from the chunk being moved itself,
+    /// and the free space around both the old and the new place is merged afterwards.
+    /// For a page granular allocator `to_offset` should be a multiple of the page size.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ptr` - The start of the chunk to be moved, invalid once the move succeeded.
+    /// * `to_offset` - Where the chunk should start, as an offset from the start of the memory.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<T>)` - The chunk at its new place.
+    /// * `Err(VallocError)` - The reason the chunk could not be moved, nothing was changed.
+    pub fn relocate_chunk<T: ?Sized>(&mut self, ptr: SmartPointer<T>, to_offset: usize) -> Result<SmartPointer<T>, VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        if !self.owns(&ptr) {
+            return Err(VallocError::NotAllocated { addr: ptr.as_ptr() as *mut u8 as usize });
+        }
+        let from = ptr.as_ptr() as *mut u8;
+        let size = self.find_chunk(&ptr).map_or(0, |x| x.size);
+        let base = self.memory.as_ptr() as usize;
+        let end = match to_offset.checked_add(size) {
+            Some(end) if end <= self.memory.len() => end,
+            _ => return Err(VallocError::OutOfBounds { addr: base.wrapping_add(to_offset) }),
+        };
+        // only free chunks (and the one being moved) may lie under the destination
+        let blocker = self.chunks.iter().find(|x| {
+            let (start, stop) = (x.ptr as usize - base, x.ptr as usize - base + x.size);
+            x.in_use && x.ptr != from && start < end && to_offset < stop
+        });
+        if let Some(blocker) = blocker {
+            let at = blocker.ptr as usize - base;
+            return Err(VallocError::DestinationInUse { at });
+        }
+
+        let to = self.memory.as_ptr().wrapping_add(to_offset) as *mut u8;
+        // the old and new place can overlap so this has to be a memmove
+        unsafe { std::ptr::copy(from, to, size); }
+
+        let mut live: Vec<Box<ChunkNode>> = std::mem::take(&mut self.chunks.list)
+            .into_iter()
+            .filter(|x| x.in_use)
+            .collect();
+        if let Some(chunk) = live.iter_mut().find(|x| x.ptr == from) { chunk.ptr = to; }
+        self.rebuild_around(live);
+        if self.deterministic { self.frontier = self.frontier.max(end); }
+
+        Ok(SmartPointer::new(NonNull::new(ptr.as_ptr().with_addr(to as usize)).expect("Chunk pointer is null!")))
+    }
+
+    // rebuilds the list in address order from the chunks in use, with the gaps between them as free chunks
+    fn rebuild_around(&mut self, live: impl IntoIterator<Item = Box<ChunkNode>>) {
+        let base = self.memory.as_ptr() as usize;
+        let mut live: Vec<_> = live.into_iter().collect();
+        live.sort_by_key(|x| x.ptr as usize);
+        let mut cursor = base as *mut u8;
+        for chunk in live {
+            if chunk.ptr > cursor {
+                self.chunks.push_back(Box::new(ChunkNode::new(cursor, chunk.ptr as usize - cursor as usize, false)));
+            }
+            cursor = chunk.ptr.wrapping_add(chunk.size);
+            self.chunks.push_back(chunk);
+        }
+        let last = base.wrapping_add(self.memory.len()) as *mut u8;
+        if last > cursor {
+            self.chunks.push_back(Box::new(ChunkNode::new(cursor, last as usize - cursor as usize, false)));
+        }
+        self.sync_header();
+    }
+
+    /// Marks the `size` bytes at `offset` (a byte offset into the memory) as in use without going through `alloc`,
+    /// e.g. to reserve a region that is managed somewhere else.
+    /// 
+    /// The range may span several free chunks but must not overlap a chunk in use.
+    /// It becomes a single chunk in use that `alloc` never hands out until it is freed with `mark_free` or `free`.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<u8>)` - A pointer to the start of the reserved chunk.
+    /// * `Err(VallocError)` - If the range is empty, out of bounds or overlaps a chunk in use.
+    pub fn mark_in_use(&mut self, offset: usize, size: usize) -> Result<SmartPointer<u8>, VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        if size == 0 { return Err(VallocError::ZeroSize); }
+        let base = self.memory.as_ptr() as usize;
+        let end = match offset.checked_add(size) {
+            Some(end) if end <= self.memory.len() => end,
+            _ => return Err(VallocError::OutOfBounds { addr: base.wrapping_add(offset) }),
+        };
+        let blocker = self.chunks.iter().find(|x| {
+            let (start, stop) = (x.ptr as usize - base, x.ptr as usize - base + x.size);
+            x.in_use && start < end && offset < stop
+        });
+        if let Some(blocker) = blocker {
+            let at = blocker.ptr as usize - base;
+            return Err(VallocError::DestinationInUse { at });
+        }
+
+        let mut chunk = Box::new(ChunkNode::from_offset(self.memory.as_ptr() as *mut u8, offset, size, true));
+        chunk.allocation_id = self.counters.next_allocation_id();
+        let ptr = chunk.smart_pointer();
+        let mut live: Vec<Box<ChunkNode>> = std::mem::take(&mut self.chunks.list)
+            .into_iter()
+            .filter(|x| x.in_use)
+            .collect();
+        live.push(chunk);
+        self.rebuild_around(live);
+
+        self.chunks.available -= size;
+        self.note_alloc(size);
+        if self.deterministic { self.frontier = self.frontier.max(end); }
+        Ok(ptr)
+    }
+
+    /// The inverse of `mark_in_use`, frees the chunk in use that spans exactly the `size` bytes at `offset`.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(())` - If the chunk was freed.
+    /// * `Err(VallocError)` - If the allocator is frozen or there is no chunk in use with that exact range.
+    pub fn mark_free(&mut self, offset: usize, size: usize) -> Result<(), VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        let addr = self.memory.as_ptr().wrapping_add(offset) as *mut u8;
+        let ptr = self.chunks.iter()
+            .find(|x| x.in_use && x.ptr == addr && x.size == size && !self.is_reserved(x.ptr))
+            .map(|x| x.smart_pointer::<u8>())
+            .ok_or(VallocError::NotAllocated { addr: addr as usize })?;
+        self.free(ptr)
+    }
+
+    /// Make sure the next `alloc` of `size` bytes will find a free chunk big enough for it,
+    /// compacting the heap first if the free space is too fragmented.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `size` - The size of the upcoming allocation, in bytes.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(Vec<(*mut u8, *mut u8)>)` - The `(old, new)` address of every chunk that was moved, empty if nothing had to move.
+    /// * `Err(VallocError)` - If the allocator is frozen or there is not enough free space in total.
+    pub fn reserve_contiguous(&mut self, size: usize) -> Result<Vec<(*mut u8, *mut u8)>, VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        if size > self.memory.len() {
+            return Err(VallocError::OutOfMemory { requested: size, capacity: self.memory.len() });
+        }
+        if size > self.chunks.available {
+            return Err(VallocError::NoContiguousSpace { requested: size, available: self.chunks.available });
+        }
+        // in deterministic mode the free chunks behind the frontier can't be used without compacting
+        let largest = if self.deterministic {
+            self.chunks.iter()
+                .filter(|x| !x.in_use)
+                .filter_map(|x| Some(x.size - self.padding_in(x, 1)?))
+                .max()
+                .unwrap_or(0)
+        } else { self.stats().largest_free };
+        if largest >= size {
+            return Ok(Vec::new());
+        }
+
+        // nothing is pinned so all of the free space ends up in one chunk at the end
+        Ok(self.defragment())
+    }
+
+    /// Slides every chunk in use towards the start of the memory, except the pinned ones which stay where they are.
+    /// 
+    /// All free space that can be reclaimed ends up merged into as few free chunks as possible.
+    /// Pointers into moved chunks are invalidated, use the returned remap to fix them up.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `pins` - Pointers to chunks that must not move.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Vec<(*mut u8, *mut u8)>` - The `(old, new)` address of every chunk that was moved, empty while frozen.
+    pub fn compact_preserving(&mut self, pins: &[&SmartPointer<u8>]) -> Vec<(*mut u8, *mut u8)> {
+        if self.frozen { return Vec::new(); }
+        self.compact_moving(|chunk| pins.iter().any(|pin| pin.as_ptr() == chunk.ptr))
+    }
+
+    /// Slides every chunk in use towards the start of the memory, leaving all of the free space in one chunk at the end.
+    /// 
+    /// The compaction is stable: chunks keep their order in memory, so if `a` was below `b` before it still is after.
+    /// Same as `compact_preserving` with nothing pinned, use the returned remap to fix up moved pointers.
+    pub fn defragment(&mut self) -> Vec<(*mut u8, *mut u8)> {
+        if self.frozen { return Vec::new(); }
+        self.compact_moving(|_| false)
+    }
+
+    /// Defragments the memory and rewrites every pointer in `live` to where its chunk moved.
+    /// 
+    /// Unlike `defragment` no pointer can be left dangling: chunks in use that aren't in `live`
+    /// are treated as pinned and stay where they are, so only pointers the caller handed over move.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `live` - Pointers to the start of chunks in use, they are updated in place.
+    /// 
+    /// # Returns
+    /// 
+    /// * `usize` - The size of the largest free chunk afterwards, nothing moves while the allocator is frozen.
+    pub fn compact_all(&mut self, live: &mut [SmartPointer<u8>]) -> usize {
+        if self.frozen { return self.stats().largest_free; }
+        let remap = self.compact_moving(|chunk| !live.iter().any(|ptr| ptr.as_ptr() == chunk.ptr));
+        for ptr in live.iter_mut() {
+            if let Some(&(_, new)) = remap.iter().find(|(old, _)| *old == ptr.as_ptr()) {
+                *ptr = SmartPointer::new(NonNull::new(new).expect("Chunks are never null!"));
+            }
+        }
+        self.stats().largest_free
+    }
+
+    /// Does part of a `defragment`, moving chunks down in address order until about `max_bytes` were moved.
+    /// 
+    /// Call it again until it reports that it is done to spread a full compaction over several frames.
+    /// The first chunk that has to move is always moved, even if it is bigger than `max_bytes`.
+    /// Pointers into moved chunks are invalidated, like with `defragment`.
+    /// 
+    /// # Returns
+    /// 
+    /// * `(usize, bool)` - The number of bytes moved, and whether the memory is now fully compacted.
+    ///   Always `(0, false)` while the allocator is frozen.
+    pub fn defragment_budgeted(&mut self, max_bytes: usize) -> (usize, bool) {
+        if self.frozen { return (0, false); }
+        let (_, moved, done) = self.compact_moving_within(|_| false, max_bytes);
+        (moved, done)
+    }
+
+    // moves every chunk in use that isn't pinned down as far as it can go
+    // chunks are moved in address order and never past the previous one, so their order is kept
+    // the chunk list is rebuilt in address order with every free region as a single chunk
+    fn compact_moving(&mut self, pinned: impl Fn(&ChunkNode) -> bool) -> Vec<(*mut u8, *mut u8)> {
+        self.compact_moving_within(pinned, usize::MAX).0
+    }
+
+    // same as `compact_moving`, but stops before a chunk that would take more than `budget` moved bytes
+    // the first chunk that has to move is always moved, so a chunk bigger than the budget can't stall it
+    // returns the remap, the number of bytes moved and whether nothing is left to move
+    fn compact_moving_within(&mut self, pinned: impl Fn(&ChunkNode) -> bool, budget: usize) -> (Vec<(*mut u8, *mut u8)>, usize, bool) {
+        let mut live: Vec<Box<ChunkNode>> = std::mem::take(&mut self.chunks.list)
+            .into_iter()
+            .filter(|x| x.in_use)
+            .collect();
+        live.sort_by_key(|x| x.ptr as usize);
+
+        let (mut remap, mut moved, mut done) = (Vec::new(), 0usize, true);
+        // everything below the cursor is either in use or pinned in place
+        let mut cursor = self.memory.as_ptr() as *mut u8;
+        for chunk in live.iter_mut() {
+            // the chunk keeps its alignment, the bytes skipped to get there are left as a free chunk
+            let to = cursor.wrapping_add(Valloc::align_offset(cursor as usize, chunk.align));
+            if done && !pinned(chunk) && !self.is_reserved(chunk.ptr) && chunk.ptr > to {
+                if !remap.is_empty() && moved.saturating_add(chunk.size) > budget {
+                    done = false;
+                } else {
+                    // the regions may overlap when sliding down so this has to be a memmove
+                    unsafe { std::ptr::copy(chunk.ptr, to, chunk.size); }
+                    remap.push((chunk.ptr, to));
+                    moved += chunk.size;
+                    chunk.ptr = to;
+                }
+            }
+            cursor = chunk.ptr.wrapping_add(chunk.size);
+        }
+
+        self.rebuild_around(live);
+        // the space the chunks moved out of is free to use again, even in deterministic mode
+        if self.deterministic { self.frontier = self.live_end(); }
+        (remap, moved, done)
+    }
+
+    /// Gives the allocator a name that is shown in its `report()`,
+    /// to tell apart several allocators in the same program.
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = Some(name.into());
+    }
+
+    /// Builder style version of `set_name`.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use valloc::allocator::Valloc;
+    /// let allocator = Valloc::new(vec![0u8; 1024].leak()).with_name("audio");
+    /// assert_eq!(allocator.name(), Some("audio"));
+    /// ```
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+
+    /// A human readable summary of the state of the allocator.
+    pub fn report(&self) -> String {
+        let stats = self.stats();
+        let mut report = format!(
+            "Valloc {}\n  Capacity:  {} bytes\n  Used:      {} bytes\n  Available: {} bytes\n  Chunks:    {} in use, {} free\n",
+            self.name.as_deref().unwrap_or("<unnamed>"),
+            stats.capacity, stats.used, stats.available, stats.live_chunks, stats.free_chunks
+        );
+        let base = self.memory.as_ptr() as usize;
+        for chunk in self.chunks.iter() {
+            let offset = chunk.ptr as usize - base;
+            report += &format!(
+                "    [{}..{}] {}\n", 
+                offset, offset + chunk.size, if chunk.in_use { "in use" } else { "free" }
+            );
+        }
+        report
+    }
+
+    /// Commit the pages of an mmap backed allocator ahead of time.
+    /// 
+    /// The pages of an mmap backed allocator are only committed by the OS the first time they are touched,
+    /// this touches every page in `[offset, offset + len)` (relative to the start of the memory) right away.
+    /// Nothing happens for any other backing, its memory is already committed.
+    pub fn prefault(&mut self, offset: usize, len: usize) {
+        self.backing.prefault(offset, len);
+    }
+
+    /// Call `callback` with the available bytes whenever an allocation leaves less than `bytes` free.
+    /// 
+    /// The callback fires once per crossing, it has to go back above the watermark
+    /// (as seen by a later allocation) before it can fire again.
+    /// Setting a new watermark replaces the old one.
+    /// 
     /// # Arguments
     /// 
-    /// * `memory` - The existing memory to be used by the Kernel.
+    /// * `bytes` - The watermark, in bytes.
+    /// * `callback` - Called with the number of available bytes.
+    pub fn set_low_memory_watermark(&mut self, bytes: usize, callback: Box<dyn FnMut(usize)>) {
+        self.watermark = Some(Watermark { bytes, callback: std::rc::Rc::new(RefCell::new(callback)), below: false });
+    }
+
+    /// Call `observer` with every allocation, free and reallocation made through this allocator,
+    /// e.g. to trace or visualize what a program does with its memory.
+    /// 
+    /// A reallocation is reported as a single `AllocEvent::Realloc`, not as the allocation and free it may be made of.
+    /// Setting a new observer replaces the old one.
+    pub fn set_observer(&mut self, observer: Box<dyn FnMut(AllocEvent)>) {
+        self.observer = Some(Observer(std::rc::Rc::new(RefCell::new(observer))));
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    fn notify(&self, event: AllocEvent) {
+        if let Some(observer) = &self.observer {
+            (observer.0.borrow_mut())(event);
+        }
+    }
+
+    /// In deterministic mode every allocation is placed right after the previous one
+    /// (rounded up to its alignment) and freed memory is never reused until `reset` is called,
+    /// so the same sequence of allocations always ends up at the same offsets.
+    /// 
+    /// Turning it on starts the allocations after the last chunk that is currently in use.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        if deterministic && !self.deterministic {
+            // the free space after the frontier has to be one chunk, however it was split up before
+            self.compact_free_list();
+            self.frontier = self.live_end();
+        }
+        self.deterministic = deterministic;
+    }
+
+    pub fn deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Free every allocation at once and start over with all of the memory in one free chunk.
+    /// 
+    /// Every pointer handed out before is invalidated. A guard region and the header of `new_in_place` stay in place.
+    /// Nothing happens while the allocator is frozen.
+    pub fn reset(&mut self) {
+        if self.frozen { return; }
+        let len = self.memory.len() - self.guard - self.header;
+        self.counters.total_freed += len - self.chunks.available;
+
+        let reserved: Vec<Box<ChunkNode>> = self.chunks.iter().filter(|x| self.is_reserved(x.ptr)).cloned().collect();
+        self.chunks = ChunkList::new(None, len);
+        self.rebuild_around(reserved);
+        self.frontier = self.header;
+        self.recent_frees = RecentFrees::default();
+        self.sync_header();
+    }
+
+    /// Stop any change through this allocator until `thaw` is called. Reading is still allowed.
+    /// 
+    /// Everything that would change a chunk or the memory refuses: the methods that return a `Result`
+    /// return `VallocError::Frozen`, the rest do nothing and return `false`, 0 or an empty list.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn thaw(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    // the index of the chunk in use that starts at `ptr`, the guard is never handed out so it isn't one
+    fn live_index(&self, ptr: *mut u8) -> Result<usize, VallocError> {
+        self.chunks.iter()
+            .position(|x| x.in_use && x.ptr == ptr && !self.is_reserved(x.ptr))
+            .ok_or(VallocError::NotAllocated { addr: ptr as usize })
+    }
+
+    // the chunk in use that holds all of `[ptr, ptr + size)`
+    // an access that starts in a live chunk but runs past its end is out of bounds, not unallocated
+    fn live_chunk_holding(&self, ptr: *mut u8, size: usize) -> Result<&ChunkNode, VallocError> {
+        let chunk = self.chunks.iter()
+            .find(|x| x.in_use && !self.is_reserved(x.ptr) && ptr >= x.ptr && (ptr as usize) < x.ptr as usize + x.size)
+            .ok_or(VallocError::NotAllocated { addr: ptr as usize })?;
+        match (ptr as usize).checked_add(size) {
+            Some(end) if end <= chunk.ptr as usize + chunk.size => Ok(chunk),
+            _ => Err(VallocError::OutOfBounds { addr: chunk.ptr as usize + chunk.size }),
+        }
+    }
+
+    /// Checks that a `T` can be read from or written to `ptr` without leaving the allocation it points into,
+    /// to be called before casting a byte pointer (e.g. one that came from C) to a wider type.
+    /// 
+    /// # Returns
     /// 
-    /// # Example
+    /// * `Ok(())` - If there are at least `size_of::<T>()` bytes left in the allocation from `ptr` onwards.
+    /// * `Err(VallocError)` - `NotAllocated` if `ptr` is not inside of an allocation,
+    ///   `OutOfBounds` (with the end of the allocation) if a `T` doesn't fit.
+    pub fn validate_typed<T>(&self, ptr: &SmartPointer<u8>) -> Result<(), VallocError> {
+        self.live_chunk_holding(ptr.as_ptr(), std::mem::size_of::<T>()).map(|_| ())
+    }
+
+    /// Read the null terminated string at `ptr`, e.g. one written by C, without the terminator.
     /// 
-    /// ```
-    /// use valloc::allocator::Valloc;
-    /// let kernel = Valloc::new(vec![0u8; 1024].leak());
-    /// ```
-    pub fn new(memory: &'a mut [u8]) -> Self {
-        // we need to cast the memory to a u8 pointer
-        // we also must adjust the len to be in bytes (which is len * `the difference between size_of::<T>() and size_of::<u8>()`)
-        let len = memory.len();
-        let chunks = {
-            let memory = memory.as_mut().as_mut_ptr();
-            ChunkList::new(
-                Some(
-                    Box::new(ChunkNode::new(
-                        memory, 
-                        len, 
-                        false
-                    ))
-                ),
-                len
-            )
-        };
+    /// The terminator has to be inside of the same allocation as `ptr`.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(&str)` - The string up to the first `0` byte.
+    /// * `Err(VallocError)` - `NotAllocated` if `ptr` is not inside of an allocation, `Unterminated` if there is no `0` byte
+    ///   before the end of the allocation, or `InvalidUtf8` if the string is not valid UTF-8.
+    pub fn read_cstr(&self, ptr: &SmartPointer<u8>) -> Result<&str, VallocError> {
+        let chunk = self.live_chunk_holding(ptr.as_ptr(), 1)?;
+        let end = chunk.ptr as usize + chunk.size;
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr() as *const u8, end - ptr.as_ptr() as usize) };
+        let len = bytes.iter().position(|&x| x == 0).ok_or(VallocError::Unterminated { end })?;
+        std::str::from_utf8(&bytes[..len]).map_err(|e| VallocError::InvalidUtf8 { at: e.valid_up_to() })
+    }
 
-        Self { memory, /*our heap chunk starts out spanning the entire memory*/ chunks }
+    /// Read a copy of the value at `ptr`, which has to lie inside of an allocation of this allocator.
+    pub fn read<T: Copy>(&self, ptr: &SmartPointer<T>) -> Result<T, VallocError> {
+        self.live_chunk_holding(ptr.as_ptr() as *mut u8, std::mem::size_of::<T>())?;
+        Ok(ptr.read())
     }
-}
 
-impl Valloc<'_> {
-    pub fn from_mem(
-        memory: NonNull<u8>, len: usize
-    ) -> Self {
-        debug_assert!(len > 0, "Memory length must be greater than 0!");
+    /// Write `value` to `ptr`, which has to lie inside of an allocation of this allocator.
+    /// 
+    /// Like `SmartPointer::write` the old value is not dropped.
+    pub fn write<T>(&mut self, ptr: &mut SmartPointer<T>, value: T) -> Result<(), VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        self.live_chunk_holding(ptr.as_ptr() as *mut u8, std::mem::size_of::<T>())?;
+        ptr.write(value);
+        Ok(())
+    }
 
-        let chunks = ChunkList::new(
-            Some(
-                Box::new(ChunkNode::new(
-                    memory.as_ptr(), 
-                    len, 
-                    false
-                ))
-            ),
-            len
-        );
-        Self { memory: unsafe{std::slice::from_raw_parts_mut(memory.as_ptr(), len)}, chunks }
+    // a pointer to `[offset, offset + size)` of the memory, if all of it is inside
+    fn offset_ptr(&self, offset: usize, size: usize) -> Result<*mut u8, VallocError> {
+        match offset.checked_add(size) {
+            Some(end) if end <= self.memory.len() => Ok(self.memory.as_ptr().wrapping_add(offset) as *mut u8),
+            _ => Err(VallocError::OutOfBounds { addr: (self.memory.as_ptr() as usize).wrapping_add(offset) }),
+        }
     }
 
-    pub fn chunks(&self) -> &ChunkList {
-        &self.chunks
+    /// Read a copy of the value at `offset` bytes from the start of the memory.
+    /// 
+    /// This is raw access to the arena, the bytes don't have to be part of an allocation
+    /// and `offset` doesn't have to be aligned for `T`.
+    pub fn read_at_offset<T: Copy>(&self, offset: usize) -> Result<T, VallocError> {
+        let ptr = self.offset_ptr(offset, std::mem::size_of::<T>())?;
+        Ok(unsafe { ptr.cast::<T>().read_unaligned() })
     }
 
-    pub fn memory(&self) -> &[u8] {
-        self.memory
+    /// Write `value` at `offset` bytes from the start of the memory, see `read_at_offset`.
+    pub fn write_at_offset<T>(&mut self, offset: usize, value: T) -> Result<(), VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        let ptr = self.offset_ptr(offset, std::mem::size_of::<T>())?;
+        unsafe { ptr.cast::<T>().write_unaligned(value); }
+        Ok(())
+    }
+
+    /// Where the memory of this allocator comes from.
+    pub fn backing_kind(&self) -> &Backing {
+        &self.backing
+    }
+
+    /// Sets whether every `alloc` (and everything built on it, like `alloc_array`) zeroes the chunk it returns.
+    /// 
+    /// Off by default, since most callers overwrite the memory anyway.
+    pub fn set_zero_on_alloc(&mut self, zero: bool) {
+        self.zero_on_alloc = zero;
+    }
+
+    pub fn zero_on_alloc(&self) -> bool {
+        self.zero_on_alloc
+    }
+
+    /// Sets whether `free` merges the freed chunk with its free neighbours (on by default).
+    /// 
+    /// Turning this off makes `free` cheaper but leaves the free list fragmented
+    /// until `compact_free_list` is called.
+    pub fn set_coalesce_on_free(&mut self, coalesce: bool) {
+        self.coalesce_on_free = coalesce;
+    }
+
+    pub fn coalesce_on_free(&self) -> bool {
+        self.coalesce_on_free
+    }
+
+    /// How fragmented the free memory is, from 0.0 (all of it in one chunk) to close to 1.0.
+    /// 
+    /// This is `1 - largest_free / available`, an allocator without free memory is not fragmented.
+    pub fn fragmentation(&self) -> f64 {
+        let available = self.chunks.available;
+        if available == 0 { return 0.0; }
+        1.0 - self.stats().largest_free as f64 / available as f64
+    }
+
+    /// Makes every `free` that leaves the heap more fragmented than `threshold` run `compact_free_list`,
+    /// `None` (the default) turns it off.
+    /// 
+    /// Unlike `defragment` this never moves memory, so pointers to chunks in use stay valid
+    /// and nothing has to be pinned. It only helps with free chunks that are next to each other.
+    pub fn set_compact_threshold_auto(&mut self, threshold: Option<f64>) {
+        self.auto_compact = threshold;
+    }
+
+    pub fn compact_threshold_auto(&self) -> Option<f64> {
+        self.auto_compact
+    }
+
+    /// Merges every run of adjacent free chunks into a single chunk.
+    /// 
+    /// The chunk list is also put back into address order.
+    /// No memory is moved so pointers to chunks in use stay valid.
+    /// 
+    /// # Returns
+    /// 
+    /// * `usize` - The number of chunks that were merged away, 0 while the allocator is frozen
+    pub fn compact_free_list(&mut self) -> usize {
+        if self.frozen { return 0; }
+        let mut chunks: Vec<Box<ChunkNode>> = std::mem::take(&mut self.chunks.list).into_iter().collect();
+        chunks.sort_by_key(|x| x.ptr as usize);
+
+        let mut merged = 0;
+        for chunk in chunks {
+            match self.chunks.list.back_mut() {
+                Some(last) if !last.in_use && !chunk.in_use 
+                    && last.ptr.wrapping_add(last.size) == chunk.ptr => {
+                    last.size += chunk.size;
+                    merged += 1;
+                },
+                _ => self.chunks.push_back(chunk),
+            }
+        }
+        self.sync_header();
+        merged
+    }
+
+    /// Leaves exactly one free chunk for every contiguous free region, the fewest the free list can have.
+    /// 
+    /// This is `compact_free_list` plus dropping empty free chunks, which nothing can be allocated from.
+    /// No memory is moved so pointers to chunks in use stay valid.
+    /// 
+    /// # Returns
+    /// 
+    /// * `usize` - The number of free chunks left, nothing is dropped while the allocator is frozen.
+    pub fn drain_free_list(&mut self) -> usize {
+        if self.frozen { return self.chunks.iter().filter(|x| !x.in_use).count(); }
+        self.compact_free_list();
+        self.chunks.list = std::mem::take(&mut self.chunks.list).into_iter()
+            .filter(|x| x.in_use || x.size > 0)
+            .collect();
+        self.sync_header();
+        self.chunks.iter().filter(|x| !x.in_use).count()
     }
 
     /// Allocate a new MemoryChunk instance.
@@ -364,18 +2840,126 @@ impl Valloc<'_> {
     /// # Returns
     /// 
     /// * `Ok(*mut T)` - A pointer to the start of the allocated chunk if successful.
-    /// * `Err(String)` - An error message if allocation fails.
+    /// * `Err(VallocError)` - The reason the allocation failed.
     /// 
     /// # Note
     /// 
     /// This method allocates in bytes.
-    pub fn alloc<T: ?Sized>(&mut self, size: usize) -> Result<SmartPointer<T>, String> {
+    pub fn alloc<T: ?Sized>(&mut self, size: usize) -> Result<SmartPointer<T>, VallocError> {
         alloc(self, size)
     }
 
+    /// Same as `alloc`, but the chunk is zeroed before it is returned, like `calloc`.
+    /// 
+    /// `free` doesn't scrub memory, so without this a chunk can still hold whatever was there before.
+    pub fn alloc_zeroed<T: ?Sized>(&mut self, size: usize) -> Result<SmartPointer<T>, VallocError> {
+        let ptr = alloc::<T>(self, size)?;
+        unsafe { std::ptr::write_bytes(ptr.as_ptr() as *mut u8, 0, size); }
+        Ok(ptr)
+    }
+
+    /// Allocate a chunk of `size` bytes that starts on a multiple of `align`.
+    /// 
+    /// Any padding in front of the chunk is split off into its own free chunk,
+    /// so it is reclaimed like any other free memory. `alloc` is the same with an `align` of 1.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `size` - The size of the chunk to be allocated, in bytes.
+    /// * `align` - The alignment of the start of the chunk, must be a power of two.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<T>)` - A pointer to the start of the allocated chunk if successful.
+    /// * `Err(VallocError)` - The reason the allocation failed, e.g. no free chunk fits `size` plus its padding.
+    pub fn alloc_aligned<T: ?Sized>(&mut self, size: usize, align: usize) -> Result<SmartPointer<T>, VallocError> {
+        alloc_aligned(self, size, align)
+    }
+
+    /// Allocate `size` bytes in the free chunk closest to `hint_offset` (from the start of the memory)
+    /// instead of the first one that fits, to keep related data close together.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `size` - The size of the chunk to be allocated, in bytes.
+    /// * `hint_offset` - Where the chunk should preferably be, it doesn't have to be free or inside of the memory.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<T>)` - A pointer to the start of the allocated chunk if successful.
+    /// * `Err(VallocError)` - The reason the allocation failed.
+    pub fn alloc_near<T: ?Sized>(&mut self, size: usize, hint_offset: usize) -> Result<SmartPointer<T>, VallocError> {
+        alloc_placed(self, size, 1, Some(hint_offset))
+    }
+
+    /// Same as `alloc`, but labels the chunk with `tag` so it can be told apart in `live_tagged`.
+    /// 
+    /// The tag follows the allocation when `realloc` or `defragment` move it.
+    pub fn alloc_tagged<T: ?Sized>(&mut self, size: usize, tag: &'static str) -> Result<SmartPointer<T>, VallocError> {
+        let ptr = self.alloc::<T>(size)?;
+        let addr = ptr.as_ptr() as *mut u8;
+        if let Some(chunk) = self.chunks.iter_mut().find(|x| x.in_use && x.ptr == addr) {
+            chunk.tag = Some(tag);
+        }
+        Ok(ptr)
+    }
+
+    /// Same as `alloc`, but also returns where the chunk starts as a byte offset into the memory.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok((SmartPointer<T>, usize))` - A pointer to the start of the allocated chunk and its offset if successful.
+    /// * `Err(VallocError)` - The reason the allocation failed.
+    pub fn alloc_at_offset<T: ?Sized>(&mut self, size: usize) -> Result<(SmartPointer<T>, usize), VallocError> {
+        let ptr = self.alloc::<T>(size)?;
+        let offset = ptr.as_ptr() as *mut u8 as usize - self.memory.as_ptr() as usize;
+        Ok((ptr, offset))
+    }
+
+    /// Frees every allocation made with `alloc_tagged` under `tag`, e.g. to drop all of the memory of a subsystem at once.
+    /// 
+    /// The frees don't merge one by one, the free list is compacted once at the end instead.
+    /// 
+    /// # Returns
+    /// 
+    /// * `usize` - The number of allocations that were freed.
+    pub fn free_by_tag(&mut self, tag: &'static str) -> usize {
+        if self.frozen { return 0; }
+        let tagged: Vec<SmartPointer<u8>> = self.chunks.iter()
+            .filter(|x| x.in_use && x.tag == Some(tag))
+            .map(|x| x.smart_pointer())
+            .collect();
+
+        let (coalesce, auto_compact) = (self.coalesce_on_free, self.auto_compact.take());
+        self.coalesce_on_free = false;
+        let freed = tagged.into_iter().filter_map(|ptr| free(self, ptr).ok()).count();
+        self.coalesce_on_free = coalesce;
+        self.auto_compact = auto_compact;
+
+        self.compact_free_list();
+        freed
+    }
+
+    /// Allocate chunks of `elem_size` bytes until one doesn't fit anymore, e.g. to warm up a pool.
+    /// 
+    /// Running out of space is not an error, whatever didn't fit is left free.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Vec<SmartPointer<T>>` - Every chunk that was allocated, in the order they were allocated.
+    pub fn fill<T: ?Sized>(&mut self, elem_size: usize) -> Vec<SmartPointer<T>> {
+        std::iter::from_fn(|| alloc(self, elem_size).ok()).collect()
+    }
+
     /// Reallocate a MemoryChunk instance.
     /// 
     /// This method reallocates the memory for a given pointer to a new size.
+    /// If the chunk is already big enough, or the chunk after it is free, it is resized in place.
+    /// Shrinking a chunk frees the tail, which can be grown back into as long as nothing else took it.
+    /// Otherwise the data is moved to a new chunk that is over-allocated (double the old size)
+    /// so that repeatedly growing a buffer only moves it a logarithmic number of times.
+    /// A `new_size` of 0 frees the chunk and returns a dangling pointer, like `realloc` in C.
+    /// A pointer to a slice comes back with its length set to the number of elements that fit in `new_size`.
     /// 
     /// # Arguments
     /// 
@@ -385,11 +2969,30 @@ impl Valloc<'_> {
     /// # Returns
     /// 
     /// * `Ok(*mut T)` - A pointer to the reallocated memory chunk if successful.
-    /// * `Err(String)` - An error message if reallocation fails.
-    pub fn realloc<T: ?Sized>(&mut self, ptr: SmartPointer<T>, new_size: usize) -> Result<SmartPointer<T>, String> {
+    /// * `Err(ReallocError)` - The reason the reallocation failed, along with the original pointer which is still valid.
+    pub fn realloc<T: ?Sized + Resizable>(&mut self, ptr: SmartPointer<T>, new_size: usize) -> Result<SmartPointer<T>, ReallocError<T>> {
         realloc(self, ptr, new_size)
     }
 
+    /// Same as `realloc`, but the resized chunk has to start on a multiple of `align`.
+    /// 
+    /// The chunk is only resized in place if it is already aligned,
+    /// otherwise the data is moved to a new chunk of exactly `new_size` bytes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `SmartPointer` - The pointer to the memory chunk to be reallocated.
+    /// * `new_size` - The new size of the memory chunk, in bytes.
+    /// * `align` - The alignment of the new chunk, must be a power of two.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<T>)` - A pointer to the reallocated memory chunk if successful.
+    /// * `Err(ReallocError)` - The reason the reallocation failed, along with the original pointer which is still valid.
+    pub fn realloc_aligned<T: ?Sized + Resizable>(&mut self, ptr: SmartPointer<T>, new_size: usize, align: usize) -> Result<SmartPointer<T>, ReallocError<T>> {
+        realloc_aligned(self, ptr, new_size, align)
+    }
+
     /// # Description
     /// 
     /// Allocate a new array of T.
@@ -403,15 +3006,115 @@ impl Valloc<'_> {
     /// # Returns
     /// 
     /// * `Ok(*mut [T])` - A pointer to the start of the allocated array if successful.
-    /// * `Err(String)` - An error message if allocation fails.
+    /// * `Err(VallocError)` - The reason the allocation failed.
     /// 
     /// # Note
     /// 
     /// This method DOES `NOT` allocate in bytes!
     /// It allocates in multiples of the size of T.
-    pub fn alloc_array<T: Sized>(&mut self, new_size: usize) -> Result<SmartPointer<[T]>, String> {
-        // because its sized we can check if the new size is a multiple of the size of T if it is then we can use alloc and safely cast the pointer to an array of T
-        let ptr = self.alloc::<[T]>(new_size * std::mem::size_of::<T>())?;
+    /// The start of the array is aligned to `align_of::<T>()`.
+    pub fn alloc_array<T: Sized>(&mut self, new_size: usize) -> Result<SmartPointer<[T]>, VallocError> {
+        // because its sized we know both the size and alignment of T so we can allocate an aligned chunk and safely cast the pointer to an array of T
+        let size = new_size.checked_mul(std::mem::size_of::<T>()).ok_or(VallocError::CapacityOverflow)?;
+        let ptr = alloc_aligned::<u8>(self, size, std::mem::align_of::<T>())
+            ?;
+        Ok(SmartPointer::new(NonNull::slice_from_raw_parts(ptr.non_null_ptr().cast::<T>(), new_size)))
+    }
+
+    /// Same as `alloc_array`, but any failure is just `None`.
+    /// 
+    /// Skips building the error, for callers in a hot loop that expects to fail.
+    pub fn try_alloc_array<T: Sized>(&mut self, count: usize) -> Option<SmartPointer<[T]>> {
+        let size = count.checked_mul(std::mem::size_of::<T>())?;
+        let ptr = alloc_aligned::<u8>(self, size, std::mem::align_of::<T>()).ok()?;
+        Some(SmartPointer::new(NonNull::slice_from_raw_parts(ptr.non_null_ptr().cast::<T>(), count)))
+    }
+
+    /// Allocate an array of `n` elements of `T` with the layout given by `Layout::array::<T>(n)`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `n` - The number of elements.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<[T]>)` - A pointer to the start of the allocated array if successful.
+    /// * `Err(VallocError)` - `CapacityOverflow` if the layout can't be built, otherwise the reason the allocation failed.
+    pub fn alloc_layout_array<T: Sized>(&mut self, n: usize) -> Result<SmartPointer<[T]>, VallocError> {
+        let layout = std::alloc::Layout::array::<T>(n).map_err(|_| VallocError::CapacityOverflow)?;
+        let ptr = alloc_aligned::<u8>(self, layout.size(), layout.align())
+            ?;
+        Ok(SmartPointer::new(NonNull::slice_from_raw_parts(ptr.non_null_ptr().cast::<T>(), n)))
+    }
+
+    /// Iterate mutably over the elements of an array allocated with `alloc_array`.
+    /// 
+    /// The iterator never goes past the end of the chunk that owns the array,
+    /// even if the length of the slice pointer says otherwise.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ptr` - The array to iterate over.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(IterMut<T>)` - An iterator over the elements if successful.
+    /// * `Err(VallocError)` - If the allocator is frozen or the array is not the start of a chunk in use.
+    pub fn array_iter_mut<T: Sized>(&mut self, ptr: &SmartPointer<[T]>) -> Result<std::slice::IterMut<'_, T>, VallocError> {
+        if self.frozen { return Err(VallocError::Frozen); }
+        let base = ptr.non_null_ptr().cast::<T>();
+        let index = self.live_index(base.as_ptr() as *mut u8)?;
+        let size = self.chunks.iter().nth(index).expect("Chunk index out of bounds!").size;
+        let len = match std::mem::size_of::<T>() {
+            0 => ptr.len(),
+            elem => ptr.len().min(size / elem),
+        };
+        // the chunk is borrowed from `self` for as long as the iterator lives
+        Ok(unsafe { std::slice::from_raw_parts_mut(base.as_ptr(), len) }.iter_mut())
+    }
+
+    /// Resize an array allocated with `alloc_array` to `new_len` elements.
+    /// 
+    /// Shrinking happens in place, the bytes of the dropped elements are freed
+    /// (destructors are not run, the memory is raw). Growing happens in place if the next chunk is free,
+    /// otherwise the elements are moved to a new chunk aligned to `align_of::<T>()`.
+    /// Either way the length of the returned slice pointer is `new_len`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ptr` - The array to be resized.
+    /// * `new_len` - The new number of elements.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<[T]>)` - A pointer to the resized array if successful.
+    /// * `Err(VallocError)` - The reason the reallocation failed.
+    pub fn realloc_array<T: Sized>(&mut self, ptr: SmartPointer<[T]>, new_len: usize) -> Result<SmartPointer<[T]>, VallocError> {
+        realloc_array(self, ptr, new_len)
+    }
+
+    /// Allocate room for a `T` aligned to `align_of::<T>()` and move the value returned by `f` into it.
+    ///
+    /// `f` is only called once the memory has been reserved, so nothing is built if the allocation fails.
+    /// A zero sized `T` is rejected like an `alloc` of 0 bytes, there would be no chunk to `free` afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Builds the value to be stored.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(SmartPointer<T>)` - A pointer to the initialized value if successful.
+    /// * `Err(VallocError::ZeroSize)` - If `T` is zero sized, `f` is not called then.
+    /// * `Err(VallocError)` - The reason the allocation failed.
+    ///
+    /// # Note
+    ///
+    /// The value is still built on the stack before it is written,
+    /// this is just the place a real placement-new would go.
+    pub fn alloc_with<T: Sized, F: FnOnce() -> T>(&mut self, f: F) -> Result<SmartPointer<T>, VallocError> {
+        let ptr = alloc_aligned::<T>(self, std::mem::size_of::<T>(), std::mem::align_of::<T>())?;
+        unsafe { std::ptr::write(ptr.as_ptr(), f()); }
         Ok(ptr)
     }
 
@@ -428,59 +3131,100 @@ impl Valloc<'_> {
     /// # Returns
     /// 
     /// * `Ok(())` - If deallocation is successful.
-    /// * `Err(String)` - An error message if deallocation fails.
-    pub fn free<T: ?Sized>(&mut self, ptr: SmartPointer<T>) -> Result<(), String> {
+    /// * `Err(VallocError)` - The reason the deallocation failed.
+    pub fn free<T: ?Sized>(&mut self, ptr: SmartPointer<T>) -> Result<(), VallocError> {
         free(self, ptr)
     }
+
+    /// Same as `free` but a pointer that is not in use is not an error.
+    /// 
+    /// # Returns
+    /// 
+    /// * `true` - If the pointer was in use and is now freed.
+    /// * `false` - If the pointer was already freed or was never allocated.
+    pub fn free_ignore_missing<T: ?Sized>(&mut self, ptr: SmartPointer<T>) -> bool {
+        self.owns(&ptr) && free(self, ptr).is_ok()
+    }
+}
+
+/// Allocate `size` bytes from the first free chunk big enough, without any alignment.
+pub fn alloc<T: ?Sized>(vallocator: &mut Valloc, size: usize) -> Result<SmartPointer<T>, VallocError> {
+    alloc_aligned(vallocator, size, 1)
+}
+
+/// Same as `alloc` but the start of the returned chunk is rounded up to `align`.
+/// Any leading padding is split off into its own free chunk.
+pub fn alloc_aligned<T: ?Sized>(vallocator: &mut Valloc, size: usize, align: usize) -> Result<SmartPointer<T>, VallocError> {
+    alloc_placed(vallocator, size, align, None)
 }
 
-pub fn alloc<T: ?Sized>(vallocator: &mut Valloc, size: usize) -> Result<SmartPointer<T>, String> {
-    // only check if not release
-    if size == 0 { return Err(format!("Size must be greater than 0 => Requested: {size} bytes")); }
+// the first free chunk that fits is used, or with a `hint` (an offset into the memory) the one closest to it
+fn alloc_placed<T: ?Sized>(vallocator: &mut Valloc, size: usize, align: usize, hint: Option<usize>) -> Result<SmartPointer<T>, VallocError> {
+    debug_assert!(align.is_power_of_two(), "Alignment must be a power of two => Requested: {align}");
+
+    if vallocator.frozen { return Err(VallocError::Frozen); }
+    if size == 0 { return Err(VallocError::ZeroSize); }
 
     // first we need to check if there is enough space in the memory
     if size > vallocator.memory.len() {
-        return Err(format!(
-            r#"
-            Not enough space in total memory!
-            Requested: {size} bytes
-            Available: {} bytes
-            "#, vallocator.memory.len()
-        ));
-    }
-
-    // then we need to check if there is enough contiguous space in the memory
-    let mut iter = vallocator.chunks.iter_mut();
-    let chunk = if let Some(v) = iter.find(|x| {
-        !x.in_use && x.size >= size
-    }) { v } else {
+        return Err(VallocError::OutOfMemory { requested: size, capacity: vallocator.memory.len() });
+    }
+
+    // whole pages only, the rounded size is what is reserved
+    let (size, align) = (vallocator.page_rounded(size).unwrap_or(usize::MAX), align.max(vallocator.page));
+    if size > vallocator.memory.len() {
+        return Err(VallocError::OutOfMemory { requested: size, capacity: vallocator.memory.len() });
+    }
+
+    // then we need to check if there is enough contiguous space (including padding) in the memory
+    let mut scanned = 0;
+    let mut fitting = vallocator.chunks.iter().enumerate().inspect(|_| scanned += 1).filter_map(|(i, x)| {
+        let padding = if x.in_use { None } else { vallocator.padding_in(x, align) }?;
+        (x.size >= size + padding).then_some((i, padding))
+    });
+    let found = match hint {
+        None => fitting.next(),
+        Some(hint) => {
+            let hint = (vallocator.memory.as_ptr() as usize).saturating_add(hint);
+            let chunks: Vec<&ChunkNode> = vallocator.chunks.iter().map(|x| x.as_ref()).collect();
+            // 0 if the hint is inside of the chunk
+            fitting.min_by_key(|&(i, _)| {
+                let (start, end) = (chunks[i].ptr as usize, chunks[i].ptr as usize + chunks[i].size);
+                start.saturating_sub(hint).max(hint.saturating_sub(end - 1))
+            })
+        },
+    };
+    vallocator.counters.note_search(scanned);
+    let (index, padding) = if let Some(found) = found { found } else {
         let available = vallocator.chunks.available;
-        
-        return Err(format!(r#"
-    Error: Not enough contiguous space in memory
-    Requested: {size} bytes
-    Available: {available} bytes
-    Chunks: {:?}
-        "#, vallocator.chunks()
-        ));
+
+        return Err(VallocError::NoContiguousSpace { requested: size, available });
     };
-    let mut new_chunk = None;
+
+    // split off the leading padding as its own free chunk, in front of the chunk to keep the address order
+    let mut index = index;
+    if padding > 0 {
+        let chunk = vallocator.chunks.iter_mut().nth(index).expect("Chunk index out of bounds!");
+        let start = chunk.ptr;
+        chunk.ptr = chunk.ptr.wrapping_add(padding);
+        chunk.size -= padding;
+        vallocator.chunks.insert_new(index, ChunkNode::new(start, padding, false));
+        index += 1;
+    }
 
     // and check if we need to split the chunk
-    if chunk.size > size {
-        // we need to split the chunk
-        new_chunk = Some(Box::new(ChunkNode::new(
-            (chunk.get_ptr::<u8>() as usize + size) as *mut u8,
-            chunk.size - size,
-            false
-        )));
-    }
-    // we also need to update the size of the chunk
+    let chunk = vallocator.chunks.iter_mut().nth(index).expect("Chunk index out of bounds!");
+    let new_chunk = if chunk.size > size {
+        Some(ChunkNode::new(chunk.ptr.wrapping_add(size), chunk.size - size, false))
+    } else { None };
     chunk.size = size;
-
-    // now we need to set the chunk to in use
     chunk.in_use = true;
-    // and get the pointer to the chunk
+    chunk.align = align;
+    chunk.allocation_id = vallocator.counters.next_allocation_id();
+    chunk.tag = None;
+    #[cfg(feature = "backtrace")]
+    { chunk.backtrace = Some(backtrace::Backtrace::new_unresolved()); }
+
     let ptr: SmartPointer<T> = {
         let ptr = chunk.ptr_unsized::<T>();
         SmartPointer::new(
@@ -488,84 +3232,251 @@ pub fn alloc<T: ?Sized>(vallocator: &mut Valloc, size: usize) -> Result<SmartPoi
         )
     };
 
-    // and update the available size    
+    // the rest goes right after the chunk
+    if let Some(new_chunk) = new_chunk {
+        vallocator.chunks.insert_new(index + 1, new_chunk);
+    }
+
+    // the padding stays free so only the chunk itself counts
+    let start = ptr.as_ptr() as *mut u8 as usize;
+    vallocator.recent_frees.forget(start, start + size);
     vallocator.chunks.available -= size;
+    vallocator.note_alloc(size);
+    if vallocator.deterministic {
+        vallocator.frontier = ptr.as_ptr() as *mut u8 as usize + size - vallocator.memory.as_ptr() as usize;
+    }
 
-    // check if we need to add a new chunk
-    if let Some(new_chunk) = new_chunk {
-        // insert the new chunk after the current chunk
-        vallocator.chunks.list.push_back(new_chunk);
+    #[cfg(debug_assertions)]
+    {
+        vallocator.assert_no_overlap(ptr.as_ptr() as *mut u8, size);
+        vallocator.chunks.assert_sorted();
+    }
+    if vallocator.zero_on_alloc {
+        unsafe { (ptr.as_ptr() as *mut u8).write_bytes(0, size); }
     }
 
-    // return the unsized type pointer
+    vallocator.sync_header();
+    vallocator.notify(AllocEvent::Alloc { ptr: ptr.as_ptr() as *mut u8, size });
     Ok(ptr)
 }
 
-pub fn free<T: ?Sized>(vallocator: &mut Valloc, ptr: SmartPointer<T>) -> Result<(), String> {
+pub fn free<T: ?Sized>(vallocator: &mut Valloc, ptr: SmartPointer<T>) -> Result<(), VallocError> {
+    if vallocator.frozen { return Err(VallocError::Frozen); }
+
     // now we need to check if the pointer is in the chunks
-    let mut iter = vallocator.chunks.iter_mut().peekable();
-    // check for any adjacent chunks that are not in use
-    // and merge them with the current chunk
-    while let Some(chunk) = iter.next() {
-        if chunk.get_ptr() == (ptr.as_ptr() as *mut u8) {
-            // check if the chunk is in use
-            if !chunk.in_use {
-                return Err(format!("Pointer is not in use: SmartPointer:{{{:#X}}}, Maybe it was already freed?", (ptr.as_ptr() as *mut u8) as usize));
-            }
+    let addr = ptr.as_ptr() as *mut u8 as usize;
+    let index = vallocator.chunks.iter()
+        .position(|x| x.get_ptr() == (ptr.as_ptr() as *mut u8))
+        .filter(|_| !vallocator.is_reserved(ptr.as_ptr() as *mut u8));
+    let Some(index) = index else {
+        // a freed chunk that was merged into the one before it is gone from the list,
+        // if it was freed recently and its memory is still free this is a double free all the same
+        let freed = vallocator.recent_frees.contains(addr) && vallocator.chunks.iter()
+            .any(|x| !x.in_use && (x.ptr as usize..x.ptr as usize + x.size).contains(&addr));
+        return Err(if freed { VallocError::DoubleFree { addr } } else { VallocError::NotAllocated { addr } });
+    };
+    let chunk = vallocator.chunks.iter_mut().nth(index).expect("Chunk index out of bounds!");
 
-            // check if the next chunk is not in use
-            if let Some(next) = iter.peek() {
-                if !next.in_use {
-                    // merge the next chunk with the current chunk
-                    chunk.size += next.size;
-                    // remove the next chunk
-                    iter.next();
-                }
-            }
+    // check if the chunk is in use, a chunk that was handed out before still has its allocation id
+    if !chunk.in_use {
+        return Err(if chunk.allocation_id == 0 { VallocError::NotAllocated { addr } } else { VallocError::DoubleFree { addr } });
+    }
 
-            // and set the chunk to not in use
-            chunk.in_use = false;
-            
-            // and update the available size
-            vallocator.chunks.available += chunk.get_size();
+    // set the chunk to not in use
+    chunk.in_use = false;
+
+    // and update the available size
+    let size = chunk.get_size();
+    vallocator.chunks.available += size;
+    vallocator.counters.total_freed += size;
+    vallocator.recent_frees.note(addr);
+
+    // merge the next chunk into the freed one, then the freed one into the chunk before it, if they are free too
+    // the list is kept in address order, so the chunk before is the one at `index - 1`
+    let forward = vallocator.coalesce_on_free && vallocator.chunks.merge_next(index);
+    let backward = vallocator.coalesce_on_free && vallocator.chunks.merge_prev(index);
+    if forward { vallocator.counters.merges_forward += 1; }
+    if backward { vallocator.counters.merges_backward += 1; }
+    if !forward && !backward { vallocator.counters.frees_without_merge += 1; }
+    #[cfg(debug_assertions)]
+    vallocator.chunks.assert_sorted();
+    vallocator.sync_header();
+    vallocator.notify(AllocEvent::Free { ptr: ptr.as_ptr() as *mut u8, size });
+    if vallocator.auto_compact.is_some_and(|threshold| vallocator.fragmentation() > threshold) {
+        vallocator.compact_free_list();
+    }
 
-            return Ok(());
-        }
+    Ok(())
+}
+
+// runs a reallocation with the observer detached, so the allocations and frees it is made of are reported as one event
+fn observed<T: ?Sized, E>(
+    vallocator: &mut Valloc, old: *mut u8, size: usize, realloc: impl FnOnce(&mut Valloc) -> Result<SmartPointer<T>, E>
+) -> Result<SmartPointer<T>, E> {
+    // a size of 0 frees the chunk, so it is reported as a free of its old size
+    let freed = if size == 0 { vallocator.chunks.iter().find(|x| x.in_use && x.ptr == old).map(|x| x.size) } else { None };
+    let observer = vallocator.observer.take();
+    let result = realloc(vallocator);
+    vallocator.observer = observer;
+
+    match (&result, freed) {
+        (Ok(_), Some(size)) => vallocator.notify(AllocEvent::Free { ptr: old, size }),
+        (Ok(new), None) => {
+            let new = new.as_ptr() as *mut u8;
+            vallocator.notify(AllocEvent::Realloc { old, new, size, moved: new != old });
+        },
+        (Err(_), _) => {},
     }
+    result
+}
 
-    // then we need to check if the pointer is in the chunks
-    Err(format!("Pointer is not in use: SmartPointer:{{{:#X}}}, Maybe it was already freed?", (ptr.as_ptr() as *mut u8) as usize))
+pub fn realloc<T: ?Sized + Resizable>(vallocator: &mut Valloc, ptr: SmartPointer<T>, nsize: usize) -> Result<SmartPointer<T>, ReallocError<T>> {
+    observed(vallocator, ptr.as_ptr() as *mut u8, nsize, |vallocator| realloc_unobserved(vallocator, ptr, nsize))
+        .map(|new| resized(new, nsize))
 }
 
-pub fn realloc<T: ?Sized>(vallocator: &mut Valloc, ptr: SmartPointer<T>, nsize: usize) -> Result<SmartPointer<T>, String> {
+/// Gives `ptr` the metadata of a chunk of `size` bytes, see `Resizable`.
+fn resized<T: ?Sized + Resizable>(ptr: SmartPointer<T>, size: usize) -> SmartPointer<T> {
+    SmartPointer::new(T::resized(ptr.non_null_ptr().cast(), size))
+}
+
+fn realloc_unobserved<T: ?Sized + Resizable>(vallocator: &mut Valloc, ptr: SmartPointer<T>, nsize: usize) -> Result<SmartPointer<T>, ReallocError<T>> {
+    // the old chunk is never touched when something goes wrong, so the caller gets it back
+    let fail = |original, kind| Err(ReallocError { original, kind });
+    if vallocator.frozen { return fail(ptr, VallocError::Frozen); }
+
     // first we need to check if the pointer is in the memory
     if (ptr.as_ptr() as *mut u8 as usize) < vallocator.memory.as_ptr() as usize || (ptr.as_ptr() as *mut u8) >= (vallocator.memory.as_ptr() as usize + vallocator.memory.len()) as *mut u8 {
-        return Err(format!("Pointer is not in memory: SmartPointer:{{{:#X}}}", (ptr.as_ptr() as *const u8) as usize));
+        let addr = ptr.as_ptr() as *mut u8 as usize;
+        return fail(ptr, VallocError::OutOfBounds { addr });
+    }
+
+    // the pointer has to be the start of a chunk in use, an interior pointer is not ours to move
+    let Some(index) = vallocator.chunks.iter()
+        .position(|x| x.in_use && x.get_ptr() == ptr.as_ptr() as *mut u8 && !vallocator.is_reserved(x.ptr)) else {
+        let addr = ptr.as_ptr() as *mut u8 as usize;
+        return fail(ptr, VallocError::NotAllocated { addr });
+    };
+
+    // like C a size of 0 just frees the chunk, what comes back is dangling and must not be used
+    if nsize == 0 {
+        let dangling = ptr.as_ptr().with_addr(NonNull::<u8>::dangling().as_ptr() as usize);
+        free(vallocator, ptr).expect("The chunk was checked to be in use!");
+        return Ok(SmartPointer::new(NonNull::new(dangling).expect("Dangling pointer is null!")));
+    }
+    let lsize = vallocator.chunks.iter().nth(index).expect("Chunk index out of bounds!").get_size();
+
+    // the chunk is already big enough (it may have been over-allocated by an earlier realloc)
+    // or the chunk right after this one is free so we can just grow into it without copying anything
+    #[cfg(test)]
+    { vallocator.last_realloc_copied = 0; }
+    // a chunk that gets smaller gives the tail back to the free list right away,
+    // it is merged with the free space after it so growing again later can still happen in place
+    if nsize < lsize {
+        vallocator.shrink_in_place(&ptr, nsize).expect("The chunk was checked to be in use!");
+        return Ok(vallocator.chunks.iter().nth(index).expect("Chunk index out of bounds!").smart_pointer());
+    }
+    if vallocator.grow_in_place(&ptr, nsize) {
+        return Ok(vallocator.chunks.iter().nth(index).expect("Chunk index out of bounds!").smart_pointer());
     }
 
     // now we let the other functions `alloc` and `free` do all the heavy lifting here :D
-    // by using them to just allocate a new chunk of size (nsize)
+    // by using them to just allocate a new chunk
     // then we place the old SmartPointer's data into the new chunk
     // and lastly we just free the old chunk
 
-    let lsize = vallocator.chunks.iter()
-        .find(|x| x.get_ptr() == ptr.as_ptr() as *mut u8)
-        .ok_or(format!("Pointer not found in chunks: SmartPointer:{{{:#X}}}", (ptr.as_ptr() as *mut u8) as usize))?
-        .get_size();
-
-    // allocate a new chunk of size (nsize)
-    let nptr: SmartPointer<T> = alloc(vallocator, nsize)?;
+    // a buffer that is moved because it grew will probably grow again,
+    // so we ask for double the size to make the next few reallocs free
+    // the new chunk has to be aligned for `T` just like the old one was
+    let nptr: SmartPointer<T> = match alloc_aligned(vallocator, nsize.max(lsize.saturating_mul(2)), T::ALIGN) {
+        Ok(nptr) => nptr,
+        Err(_) => match alloc_aligned(vallocator, nsize, T::ALIGN) {
+            Ok(nptr) => nptr,
+            Err(kind) => return fail(ptr, kind),
+        },
+    };
     {
         // copy the data from the old chunk to the new chunk
         // first we are going to reinterpret the pointers as u8 pointers
         let (optr, nptr) = (ptr.as_ptr() as *mut u8, nptr.as_ptr() as *mut u8);
-        // then we are going to copy the data from the old chunk to the new chunk
-        unsafe { std::ptr::copy(optr, nptr, lsize * std::mem::size_of::<u8>()); }
+        // then we are going to copy the data from the old chunk to the new chunk, never more than fits in it
+        let copied = lsize.min(nsize);
+        unsafe { std::ptr::copy(optr, nptr, copied); }
+        #[cfg(test)]
+        { vallocator.last_realloc_copied = copied; }
     }
 
     // free the old chunk
-    free(vallocator, ptr)?;
+    vallocator.relocated(ptr, &nptr).expect("The old chunk was checked to be in use!");
 
     // return the new pointer
     Ok(nptr)
 }
+
+pub fn realloc_aligned<T: ?Sized + Resizable>(vallocator: &mut Valloc, ptr: SmartPointer<T>, nsize: usize, align: usize) -> Result<SmartPointer<T>, ReallocError<T>> {
+    observed(vallocator, ptr.as_ptr() as *mut u8, nsize, |vallocator| realloc_aligned_unobserved(vallocator, ptr, nsize, align))
+        .map(|new| resized(new, nsize))
+}
+
+fn realloc_aligned_unobserved<T: ?Sized + Resizable>(vallocator: &mut Valloc, ptr: SmartPointer<T>, nsize: usize, align: usize) -> Result<SmartPointer<T>, ReallocError<T>> {
+    debug_assert!(align.is_power_of_two(), "Alignment must be a power of two => Requested: {align}");
+
+    // freeing and every error is the same as for a plain realloc, only where the chunk ends up differs
+    if vallocator.frozen || nsize == 0 || !vallocator.owns(&ptr) { return realloc(vallocator, ptr, nsize); }
+
+    let index = vallocator.chunks.iter()
+        .position(|x| x.get_ptr() == ptr.as_ptr() as *mut u8)
+        .expect("The chunk was checked to be in use!");
+    #[cfg(test)]
+    { vallocator.last_realloc_copied = 0; }
+    if (ptr.as_ptr() as *mut u8 as usize).is_multiple_of(align) && vallocator.grow_in_place(&ptr, nsize) {
+        return Ok(vallocator.chunks.iter().nth(index).expect("Chunk index out of bounds!").smart_pointer());
+    }
+    let lsize = vallocator.chunks.iter().nth(index).expect("Chunk index out of bounds!").get_size();
+
+    let nptr: SmartPointer<T> = match alloc_aligned(vallocator, nsize, align) {
+        Ok(nptr) => nptr,
+        Err(kind) => return Err(ReallocError { original: ptr, kind }),
+    };
+    // when shrinking only what fits is kept
+    let copied = lsize.min(nsize);
+    unsafe { std::ptr::copy(ptr.as_ptr() as *mut u8, nptr.as_ptr() as *mut u8, copied); }
+    #[cfg(test)]
+    { vallocator.last_realloc_copied = copied; }
+    vallocator.relocated(ptr, &nptr).expect("The old chunk was checked to be in use!");
+
+    Ok(nptr)
+}
+
+pub fn realloc_array<T: Sized>(vallocator: &mut Valloc, ptr: SmartPointer<[T]>, new_len: usize) -> Result<SmartPointer<[T]>, VallocError> {
+    let size = new_len.saturating_mul(std::mem::size_of::<T>());
+    observed(vallocator, ptr.as_ptr() as *mut u8, size, |vallocator| realloc_array_unobserved(vallocator, ptr, new_len))
+}
+
+fn realloc_array_unobserved<T: Sized>(vallocator: &mut Valloc, ptr: SmartPointer<[T]>, new_len: usize) -> Result<SmartPointer<[T]>, VallocError> {
+    if vallocator.frozen { return Err(VallocError::Frozen); }
+    let new_size = new_len.checked_mul(std::mem::size_of::<T>()).ok_or(VallocError::CapacityOverflow)?;
+    if new_size == 0 { return Err(VallocError::ZeroSize); }
+
+    let base = ptr.non_null_ptr().cast::<T>();
+    let index = vallocator.live_index(base.as_ptr() as *mut u8)?;
+    let size = vallocator.chunks.iter().nth(index).expect("Chunk index out of bounds!").size;
+
+    // shrinking (or growing into slack) never moves the data, the tail is just given back
+    if new_size <= size {
+        let keep = vallocator.page_rounded(new_size).expect("A smaller size can't overflow!").min(size);
+        vallocator.shrink_chunk(index, keep);
+        return Ok(SmartPointer::new(NonNull::slice_from_raw_parts(base, new_len)));
+    }
+    if vallocator.grow_in_place(&ptr, new_size) {
+        return Ok(SmartPointer::new(NonNull::slice_from_raw_parts(base, new_len)));
+    }
+
+    // the elements have to move, only the ones that are actually there get copied
+    let nptr = alloc_aligned::<u8>(vallocator, new_size, std::mem::align_of::<T>())?;
+    let old_size = (ptr.len() * std::mem::size_of::<T>()).min(size);
+    unsafe { std::ptr::copy_nonoverlapping(base.as_ptr() as *const u8, nptr.as_ptr(), old_size); }
+    vallocator.relocated(ptr, &nptr)?;
+
+    Ok(SmartPointer::new(NonNull::slice_from_raw_parts(nptr.non_null_ptr().cast::<T>(), new_len)))
+}
@@ -0,0 +1,138 @@
+use crate::{allocator::SmartPointer, validate::ConsistencyIssue};
+
+/// The errors that can be returned by a `Valloc`.
+///
+/// Pointers are stored as plain addresses so the error can
+/// outlive (and be sent away from) the allocator it came from.
+/// Every field is a plain number, so building one never allocates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VallocError {
+    /// An allocation of 0 bytes was requested.
+    ZeroSize,
+    /// The request is bigger than the whole memory of the allocator.
+    OutOfMemory { requested: usize, capacity: usize },
+    /// The size of the request doesn't fit in a `usize` (or an `isize`, like `Layout` requires).
+    CapacityOverflow,
+    /// There is no free chunk big enough for the request.
+    NoContiguousSpace { requested: usize, available: usize },
+    /// The pointer lies outside of the memory of the allocator.
+    OutOfBounds { addr: usize },
+    /// The pointer is not the start of a chunk that is currently in use.
+    NotAllocated { addr: usize },
+    /// The pointer is the start of a chunk that was allocated and has already been freed.
+    DoubleFree { addr: usize },
+    /// A chunk can only be split strictly inside of it.
+    InvalidSplit { at: usize, size: usize },
+    /// The second chunk doesn't start right where the first one ends.
+    NotAdjacent { first: usize, second: usize },
+    /// A slice of `len` elements doesn't fit into an allocation of `capacity` elements.
+    SliceTooLong { len: usize, capacity: usize },
+    /// The destination of a move overlaps a chunk that is in use, `at` is its offset from the start of the memory.
+    DestinationInUse { at: usize },
+    /// No `0` byte was found between the start of a C string and `end`, the end of the allocation it is in.
+    Unterminated { end: usize },
+    /// A C string is not valid UTF-8, `at` is the offset of the first invalid byte from the start of the string.
+    InvalidUtf8 { at: usize },
+    /// Two allocators that have to manage the exact same memory don't.
+    BackingMismatch,
+    /// The allocator was frozen with `Valloc::freeze`, nothing can be changed until it is thawed.
+    Frozen,
+    /// The memory given to `Valloc::open_in_place` doesn't start with a header written by `Valloc::new_in_place`.
+    MissingHeader,
+    /// The chunk list outgrew the header of `Valloc::new_in_place`, which only has room for `capacity` chunks.
+    HeaderFull { chunks: usize, capacity: usize },
+    /// `Valloc::merge_with` can't absorb an allocator with a header of `Valloc::new_in_place`, it would end up in the middle of the arena.
+    UnexpectedHeader,
+    /// `Valloc::merge_with` can't join allocators that hand out memory in pages of different sizes.
+    PageSizeMismatch { ours: usize, theirs: usize },
+    /// A chunk list given to `Valloc::try_from_raw_parts` doesn't describe a valid heap.
+    Inconsistent { issue: ConsistencyIssue },
+    /// The OS refused to map the requested memory.
+    #[cfg(all(unix, feature = "mmap"))]
+    MapFailed { len: usize, errno: i32 },
+}
+
+impl core::fmt::Display for VallocError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ZeroSize => write!(f, "Size must be greater than 0"),
+            Self::OutOfMemory { requested, capacity } => write!(
+                f, "Not enough space in total memory! Requested: {requested} bytes, Capacity: {capacity} bytes"
+            ),
+            Self::CapacityOverflow => write!(f, "Requested size overflows the address space!"),
+            Self::NoContiguousSpace { requested, available } => write!(
+                f, "Not enough contiguous space in memory! Requested: {requested} bytes, Available: {available} bytes"
+            ),
+            Self::OutOfBounds { addr } => write!(f, "Pointer is not in memory: SmartPointer:{{{addr:#X}}}"),
+            Self::NotAllocated { addr } => write!(
+                f, "Pointer is not in use: SmartPointer:{{{addr:#X}}}, It was never allocated!"
+            ),
+            Self::DoubleFree { addr } => write!(
+                f, "Pointer is not in use: SmartPointer:{{{addr:#X}}}, It was already freed!"
+            ),
+            Self::InvalidSplit { at, size } => write!(
+                f, "Can't split a chunk of {size} bytes at offset {at}!"
+            ),
+            Self::NotAdjacent { first, second } => write!(
+                f, "Chunks are not next to each other in memory: SmartPointer:{{{first:#X}}}, SmartPointer:{{{second:#X}}}"
+            ),
+            Self::SliceTooLong { len, capacity } => write!(
+                f, "Slice of {len} elements doesn't fit in {capacity} elements!"
+            ),
+            Self::DestinationInUse { at } => write!(f, "Destination at offset {at} overlaps a chunk in use!"),
+            Self::Unterminated { end } => write!(f, "String has no null terminator before the end of its allocation at {end:#X}!"),
+            Self::InvalidUtf8 { at } => write!(f, "String is not valid UTF-8 at byte {at}!"),
+            Self::BackingMismatch => write!(f, "Allocators don't manage the same memory!"),
+            Self::Frozen => write!(f, "Allocator is frozen, thaw it first!"),
+            Self::MissingHeader => write!(f, "Memory doesn't start with an allocator header!"),
+            Self::HeaderFull { chunks, capacity } => write!(
+                f, "Allocator header has room for {capacity} chunks but the chunk list has {chunks}!"
+            ),
+            Self::UnexpectedHeader => write!(f, "Can't merge an allocator that starts with a header!"),
+            Self::PageSizeMismatch { ours, theirs } => write!(
+                f, "Can't merge allocators with different page sizes! Ours: {ours} bytes, Theirs: {theirs} bytes"
+            ),
+            Self::Inconsistent { issue } => write!(f, "Chunk list is inconsistent! {issue:?}"),
+            #[cfg(all(unix, feature = "mmap"))]
+            Self::MapFailed { len, errno } => write!(
+                f, "Failed to mmap {len} bytes: {}", std::io::Error::from_raw_os_error(*errno)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VallocError {}
+
+/// The error returned by `Valloc::realloc`.
+/// 
+/// A failed realloc never frees or moves the old chunk,
+/// so the original pointer is handed back and can still be used.
+pub struct ReallocError<T: ?Sized> {
+    /// The pointer that was passed to `realloc`, untouched.
+    pub original: SmartPointer<T>,
+    /// Why the reallocation failed.
+    pub kind: VallocError,
+}
+
+impl<T: ?Sized> From<ReallocError<T>> for VallocError {
+    fn from(value: ReallocError<T>) -> Self {
+        value.kind
+    }
+}
+
+impl<T: ?Sized> core::fmt::Debug for ReallocError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ReallocError")
+            .field("original", &(self.original.as_ptr() as *mut u8))
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl<T: ?Sized> core::fmt::Display for ReallocError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl<T: ?Sized> std::error::Error for ReallocError<T> {}
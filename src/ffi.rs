@@ -7,6 +7,14 @@ pub extern "C" fn valloc(size: usize) -> *mut () {
     get_allocator().alloc::<()>(size).unwrap().as_ptr().cast()
 }
 
+/// Allocates zeroed memory for an array of `nmemb` elements of `size` bytes each using the vCPU allocator, like `calloc`.
+/// Returns NULL if `nmemb * size` overflows or the allocation fails.
+#[no_mangle]
+pub extern "C" fn vcalloc(nmemb: usize, size: usize) -> *mut () {
+    let Some(total) = nmemb.checked_mul(size) else { return std::ptr::null_mut(); };
+    get_allocator().alloc_zeroed::<()>(total).map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr())
+}
+
 /// Frees the memory block pointed to by `ptr` using the vCPU allocator.
 #[no_mangle]
 pub extern "C" fn vfree(ptr: *mut ()) {
@@ -14,10 +22,11 @@ pub extern "C" fn vfree(ptr: *mut ()) {
 }
 
 /// Resizes the memory block pointed to by `ptr` to the specified size using the vCPU allocator.
-/// Returns a raw pointer to the resized memory block.
+/// Returns a raw pointer to the resized memory block, or NULL if `size` is 0 (the block is freed).
 #[no_mangle]
 pub extern "C" fn vrealloc(ptr: *mut (), size: usize) -> *mut std::ffi::c_void {
-    get_allocator().realloc::<()>(unsafe{SmartPointer::new_unchecked(ptr.cast())}, size).unwrap().as_ptr().cast()
+    let ptr = get_allocator().realloc::<()>(unsafe{SmartPointer::new_unchecked(ptr.cast())}, size).unwrap();
+    if size == 0 { std::ptr::null_mut() } else { ptr.as_ptr().cast() }
 }
 
 /// Allocates a block of memory of the specified size using the vCPU allocator.
@@ -31,20 +40,28 @@ pub extern "C" fn virtual_alloc(allocator: &'static mut Valloc, size: usize) ->
 }
 
 /// Frees the memory block pointed to by `ptr` using the vCPU allocator.
+/// Returns false (and leaves the allocator untouched) if `ptr` was not allocated by `allocator`.
 #[no_mangle]
-pub extern "C" fn virtual_free(allocator: &'static mut Valloc, ptr: *mut ()) {
-    match allocator.free::<()>(unsafe{SmartPointer::new_unchecked(ptr.cast())}) {
-        Ok(_) => (),
+pub extern "C" fn virtual_free(allocator: &'static mut Valloc, ptr: *mut ()) -> bool {
+    let ptr = unsafe{SmartPointer::new_unchecked(ptr.cast::<()>())};
+    if !allocator.owns(&ptr) { return false; }
+
+    match allocator.free::<()>(ptr) {
+        Ok(_) => true,
         Err(e) => panic!("{e}"),
     }
 }
 
 /// Resizes the memory block pointed to by `ptr` to the specified size using the vCPU allocator.
-/// Returns a raw pointer to the resized memory block.
+/// Returns a raw pointer to the resized memory block, or NULL if `size` is 0 (the block is freed).
+/// Returns NULL (and leaves the allocator untouched) if `ptr` was not allocated by `allocator`.
 #[no_mangle]
 #[allow(unused_assignments)]
 pub extern "C" fn virtual_realloc(allocator: &'static mut Valloc, mut ptr: *mut (), size: usize) -> *mut () {
+    if !allocator.owns(&unsafe{SmartPointer::new_unchecked(ptr.cast::<()>())}) { return std::ptr::null_mut(); }
+
     match allocator.realloc::<()>(unsafe{SmartPointer::new_unchecked(ptr.cast())}, size) {
+        Ok(_) if size == 0 => std::ptr::null_mut(),
         Ok(val) => {
             ptr = std::ptr::null_mut();
             val.as_ptr()
@@ -53,6 +70,27 @@ pub extern "C" fn virtual_realloc(allocator: &'static mut Valloc, mut ptr: *mut
     }
 }
 
+/// Tries to resize the memory block pointed to by `ptr` to the specified size without moving it.
+/// Returns true if the block now holds at least `size` bytes at the same address.
+/// Shrinking always succeeds and gives the rest of the block back to the allocator.
+/// Returns false (and leaves the allocator untouched) if the block would have to move or `ptr` was not allocated by `allocator`.
+#[no_mangle]
+pub extern "C" fn vrealloc_inplace(allocator: &'static mut Valloc, ptr: *mut (), size: usize) -> bool {
+    let ptr = unsafe{SmartPointer::new_unchecked(ptr.cast::<()>())};
+    match allocator.usable_size(&ptr) {
+        Ok(usable) if size > 0 && size < usable => allocator.shrink_in_place(&ptr, size).is_ok(),
+        Ok(_) => size > 0 && allocator.grow_in_place(&ptr, size),
+        Err(_) => false,
+    }
+}
+
+/// Returns the number of usable bytes in the memory block pointed to by `ptr`, which may be more than was requested.
+/// Returns 0 if `ptr` was not allocated by `allocator`.
+#[no_mangle]
+pub extern "C" fn vmalloc_usable_size(allocator: &'static Valloc, ptr: *mut ()) -> usize {
+    allocator.usable_size(&unsafe{SmartPointer::new_unchecked(ptr.cast::<()>())}).unwrap_or(0)
+}
+
 /// Initializes the vCPU allocator with the specified size.
 #[no_mangle]
 pub extern "C" fn global_init(size: usize) {
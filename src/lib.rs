@@ -1,8 +1,21 @@
-#![feature(generic_arg_infer)]
-#![feature(allocator_api)]
+// the `Allocator` trait is only available on nightly,
+// everything else works on stable
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
 
 pub mod allocator;
+pub mod error;
 pub mod ffi;
+pub mod ring;
+pub mod scope;
+pub mod snapshot;
+pub mod stats;
+pub mod validate;
+pub mod vec;
+#[cfg(all(unix, feature = "mmap"))]
+pub mod mmap;
+
+#[cfg(feature = "nightly")]
+pub use allocator::{vec_in, vec_with_capacity_in};
 
 #[cfg(test)]
 pub mod tests;
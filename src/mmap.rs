@@ -0,0 +1,102 @@
+use std::ptr::NonNull;
+
+use crate::error::VallocError;
+
+/// An anonymous private memory mapping.
+///
+/// The address space is reserved up front but pages are only committed
+/// by the OS once they are touched, so large arenas are cheap to create.
+/// The mapping is unmapped when this is dropped.
+#[derive(Debug)]
+pub struct MmapRegion {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// the mapping is plain memory that nothing else has a handle to
+unsafe impl Send for MmapRegion {}
+unsafe impl Sync for MmapRegion {}
+
+impl MmapRegion {
+    /// Maps `len` bytes of anonymous memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - The size of the mapping, in bytes.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(MmapRegion)` - The new mapping if successful.
+    /// * `Err(VallocError)` - The reason `mmap` failed.
+    pub fn new(len: usize) -> Result<Self, VallocError> {
+        if len == 0 { return Err(VallocError::ZeroSize); }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | map_noreserve(),
+                -1,
+                0
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            let errno = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+            return Err(VallocError::MapFailed { len, errno });
+        }
+
+        Ok(Self { ptr: NonNull::new(ptr.cast()).expect("mmap returned null!"), len })
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Commits the pages covering `[offset, offset + len)` by touching one byte in each of them,
+    /// so the first real access doesn't have to wait for a page fault.
+    /// 
+    /// The range is clamped to the mapping and the contents are left as they were.
+    pub fn prefault(&self, offset: usize, len: usize) {
+        let end = offset.saturating_add(len).min(self.len);
+        let page = page_size();
+        // start at the page that contains `offset`
+        let mut at = offset - offset % page;
+        while at < end {
+            // a write is needed, a read of a private anonymous page only maps the shared zero page
+            unsafe {
+                let byte = self.ptr.as_ptr().add(at);
+                byte.write_volatile(byte.read_volatile());
+            }
+            at += page;
+        }
+    }
+}
+
+/// The size of a page of memory on this system, in bytes.
+pub fn page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+impl Drop for MmapRegion {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len); }
+    }
+}
+
+// don't reserve swap for the whole region where the OS supports it
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn map_noreserve() -> libc::c_int { libc::MAP_NORESERVE }
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn map_noreserve() -> libc::c_int { 0 }
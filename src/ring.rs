@@ -0,0 +1,120 @@
+use std::ptr::NonNull;
+
+use crate::{allocator::SmartPointer, error::VallocError};
+
+/// A first in first out allocator over a ring buffer.
+/// 
+/// Allocating moves the head forward and freeing moves the tail forward,
+/// there is no chunk list so it is only useful when allocations are freed in the order they were made,
+/// like the messages of a stream.
+/// An allocation never wraps around the end of the buffer, if it doesn't fit before the end
+/// it starts over at the front and the bytes left at the end are skipped until the tail passes them.
+/// 
+/// # Example
+/// 
+/// ```
+/// use valloc::ring::RingValloc;
+/// let mut ring = RingValloc::new(vec![0u8; 64].leak());
+/// let a = ring.alloc(48).unwrap();
+/// ring.free_oldest(48).unwrap();
+/// // 32 bytes don't fit after `a` so they wrap around to the front
+/// let b = ring.alloc(32).unwrap();
+/// assert_eq!(b.as_ptr() as *mut u8, a.as_ptr() as *mut u8);
+/// ```
+#[derive(Debug)]
+pub struct RingValloc<'a> {
+    memory: &'a mut [u8],
+    head: usize,
+    tail: usize,
+    // where the data before the front of the buffer ends, only meaningful while `wrapped`
+    end: usize,
+    wrapped: bool,
+    used: usize,
+}
+
+impl<'a> RingValloc<'a> {
+    pub fn new(memory: &'a mut [u8]) -> Self {
+        let end = memory.len();
+        Self { memory, head: 0, tail: 0, end, wrapped: false, used: 0 }
+    }
+
+    /// Allocate `size` bytes at the head of the ring.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(SmartPointer<[u8]>)` - The allocated bytes if successful.
+    /// * `Err(VallocError)` - If there is no room between the head and the tail.
+    pub fn alloc(&mut self, size: usize) -> Result<SmartPointer<[u8]>, VallocError> {
+        if size == 0 { return Err(VallocError::ZeroSize); }
+        if size > self.memory.len() {
+            return Err(VallocError::OutOfMemory { requested: size, capacity: self.memory.len() });
+        }
+
+        let at = if self.wrapped {
+            // the free space is between the head and the tail
+            if self.head + size > self.tail { return Err(self.no_space(size)); }
+            self.head
+        } else if self.head + size <= self.memory.len() {
+            self.head
+        } else if size <= self.tail {
+            // start over at the front, the bytes after the head are skipped
+            self.end = self.head;
+            self.wrapped = true;
+            0
+        } else {
+            return Err(self.no_space(size));
+        };
+
+        self.head = at + size;
+        self.used += size;
+        let ptr = NonNull::new(self.memory[at..at + size].as_mut_ptr()).expect("Ring memory is null!");
+        Ok(SmartPointer::new(NonNull::slice_from_raw_parts(ptr, size)))
+    }
+
+    /// Free the oldest `size` bytes by moving the tail forward.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(())` - If the bytes were freed.
+    /// * `Err(VallocError)` - If less than `size` bytes are allocated.
+    pub fn free_oldest(&mut self, size: usize) -> Result<(), VallocError> {
+        if size > self.used {
+            let addr = self.memory.as_ptr() as usize + self.head;
+            return Err(VallocError::NotAllocated { addr });
+        }
+
+        if self.wrapped {
+            let before_front = self.end - self.tail;
+            if size < before_front {
+                self.tail += size;
+            } else {
+                // the tail passed the skipped bytes and follows the head from the front
+                self.tail = size - before_front;
+                self.wrapped = false;
+            }
+        } else {
+            self.tail += size;
+        }
+        self.used -= size;
+
+        // an empty ring starts over at the front so the next allocation gets the whole buffer
+        if self.used == 0 {
+            (self.head, self.tail, self.wrapped) = (0, 0, false);
+        }
+        Ok(())
+    }
+
+    /// The number of bytes currently allocated.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// The size of the whole ring, in bytes.
+    pub fn capacity(&self) -> usize {
+        self.memory.len()
+    }
+
+    fn no_space(&self, requested: usize) -> VallocError {
+        VallocError::NoContiguousSpace { requested, available: self.memory.len() - self.used }
+    }
+}
@@ -0,0 +1,62 @@
+use std::ptr::NonNull;
+
+use crate::allocator::{SmartPointer, Valloc};
+
+/// Frees everything allocated through it when it goes out of scope, made with `Valloc::scope`.
+/// 
+/// The guard borrows the allocator for as long as it lives and derefs to it,
+/// so allocations in the scope are made through the guard.
+/// Chunks that were allocated before the guard was made are never touched,
+/// even if they are freed or reallocated in the scope.
+/// 
+/// # Example
+/// 
+/// ```
+/// use valloc::allocator::Valloc;
+/// let mut allocator = Valloc::new(vec![0u8; 1024].leak());
+/// {
+///     let mut scope = allocator.scope();
+///     scope.alloc::<[u8]>(512).unwrap();
+/// }
+/// assert!(allocator.is_empty());
+/// ```
+pub struct ScopeGuard<'v, 'a> {
+    valloc: &'v mut Valloc<'a>,
+    // every allocation with a higher id was made in the scope
+    checkpoint: u64,
+}
+
+impl<'v, 'a> ScopeGuard<'v, 'a> {
+    pub fn new(valloc: &'v mut Valloc<'a>) -> Self {
+        let checkpoint = valloc.allocation_count();
+        Self { valloc, checkpoint }
+    }
+}
+
+impl<'a> std::ops::Deref for ScopeGuard<'_, 'a> {
+    type Target = Valloc<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.valloc
+    }
+}
+
+impl std::ops::DerefMut for ScopeGuard<'_, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.valloc
+    }
+}
+
+impl Drop for ScopeGuard<'_, '_> {
+    fn drop(&mut self) {
+        let made_in_scope: Vec<*mut u8> = self.valloc.chunks().iter()
+            .filter(|x| x.is_in_use() && x.get_allocation_id() > self.checkpoint)
+            .map(|x| x.get_ptr())
+            .collect();
+        for ptr in made_in_scope {
+            let ptr = SmartPointer::new(NonNull::new(ptr).expect("Chunk pointer is null!"));
+            // a frozen allocator keeps the chunks, there is nothing else to do with the error in a drop
+            let _ = self.valloc.free::<u8>(ptr);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use crate::allocator::ChunkListRepr;
+
+/// The granularity `Valloc::diff_since` compares memory at, in bytes.
+pub const DIFF_BLOCK: usize = 64;
+
+/// A full copy of the memory and chunk list of a `Valloc`, taken with `Valloc::snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Snapshot {
+    /// Every byte of the managed memory, free or not.
+    pub memory: Vec<u8>,
+    /// The chunk list at the time of the snapshot.
+    pub chunks: ChunkListRepr,
+}
+
+/// What changed in a `Valloc` since a `Snapshot`, made with `Valloc::diff_since`
+/// and replayed on another allocator with `Valloc::apply_diff`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SnapshotDiff {
+    /// The changed bytes as `(offset, bytes)`, in address order.
+    pub ranges: Vec<(usize, Vec<u8>)>,
+    /// The new chunk list, `None` if it is the same as in the snapshot.
+    pub chunks: Option<ChunkListRepr>,
+}
+
+impl SnapshotDiff {
+    /// Checks if nothing changed at all.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.chunks.is_none()
+    }
+
+    /// The number of bytes carried by the diff, without the chunk list.
+    pub fn changed_bytes(&self) -> usize {
+        self.ranges.iter().map(|(_, bytes)| bytes.len()).sum()
+    }
+}
@@ -0,0 +1,99 @@
+/// A snapshot of the state of a `Valloc` heap, taken with `Valloc::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeapStats {
+    /// The total size of the managed memory, in bytes.
+    pub capacity: usize,
+    /// The bytes currently handed out.
+    pub used: usize,
+    /// The bytes currently free.
+    pub available: usize,
+    /// The number of chunks in use.
+    pub live_chunks: usize,
+    /// The number of free chunks.
+    pub free_chunks: usize,
+    /// The size of the biggest free chunk (the largest allocation that can currently succeed).
+    pub largest_free: usize,
+    /// The bytes allocated over the lifetime of the allocator.
+    pub total_allocated: usize,
+    /// The bytes freed over the lifetime of the allocator.
+    pub total_freed: usize,
+    /// The highest `used` has ever been.
+    pub peak_used: usize,
+}
+
+/// The difference between two `HeapStats` snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsDelta {
+    /// The bytes allocated between the two snapshots, 0 if the earlier one has the higher total.
+    pub allocated: usize,
+    /// The bytes freed between the two snapshots, 0 if the earlier one has the higher total.
+    pub freed: usize,
+    /// The net change in used bytes (negative if more was freed than allocated).
+    pub used: isize,
+    /// How much the peak usage grew between the two snapshots.
+    pub peak_increase: usize,
+}
+
+/// How often freed chunks were merged with their neighbours, taken with `Valloc::coalesce_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CoalesceStats {
+    /// Frees that merged the chunk after the freed one into it.
+    pub merges_forward: usize,
+    /// Frees that merged the freed chunk into the chunk before it.
+    pub merges_backward: usize,
+    /// Frees that left the freed chunk on its own.
+    pub frees_without_merge: usize,
+}
+
+/// What an allocator did, as reported to the observer set with `Valloc::set_observer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocEvent {
+    /// A chunk of `size` bytes was handed out at `ptr`.
+    Alloc { ptr: *mut u8, size: usize },
+    /// The chunk of `size` bytes at `ptr` was freed.
+    Free { ptr: *mut u8, size: usize },
+    /// The chunk at `old` was resized to `size` bytes and now starts at `new`,
+    /// `moved` tells if the data had to be copied. A `realloc` to 0 bytes is reported as a `Free`.
+    Realloc { old: *mut u8, new: *mut u8, size: usize, moved: bool },
+}
+
+/// A chunk that was still in use when `Valloc::leak_check` was called.
+#[derive(Debug, Clone)]
+pub struct Leak {
+    /// The start of the chunk.
+    pub ptr: *mut u8,
+    /// The size of the chunk, in bytes.
+    pub size: usize,
+    /// Where the chunk was allocated.
+    #[cfg(feature = "backtrace")]
+    pub backtrace: Option<backtrace::Backtrace>,
+}
+
+impl HeapStats {
+    /// Computes what changed since an `earlier` snapshot of the same allocator.
+    /// 
+    /// The lifetime totals only ever grow, so `allocated`, `freed` and `peak_increase` saturate at 0
+    /// instead of overflowing when the snapshots are swapped or come from different allocators.
+    /// `used` is signed and goes negative when the heap shrank.
+    /// 
+    /// # Example
+    /// 
+    /// ```
+    /// use valloc::allocator::Valloc;
+    /// let mut allocator = Valloc::new(vec![0u8; 1024].leak());
+    /// 
+    /// let before = allocator.stats();
+    /// let ptr = allocator.alloc::<u8>(100).unwrap();
+    /// let delta = allocator.stats().delta(&before);
+    /// 
+    /// assert_eq!(delta.used, 100);
+    /// ```
+    pub fn delta(&self, earlier: &HeapStats) -> StatsDelta {
+        StatsDelta {
+            allocated: self.total_allocated.saturating_sub(earlier.total_allocated),
+            freed: self.total_freed.saturating_sub(earlier.total_freed),
+            used: self.used as isize - earlier.used as isize,
+            peak_increase: self.peak_used.saturating_sub(earlier.peak_used),
+        }
+    }
+}
@@ -1,10 +1,57 @@
-use crate::allocator::{global_allocator, valloc_init, Valloc};
+use crate::allocator::{ChunkNode, SmartPointer, Span, Valloc};
+#[cfg(feature = "nightly")]
+use crate::allocator::{get_allocator, global_allocator, global_allocator_unsync, valloc_init, GlobalValloc};
+use crate::error::VallocError;
+use crate::stats::AllocEvent;
+use crate::validate::ConsistencyIssue;
+use crate::vec::VVec;
+use crate::ring::RingValloc;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
 use std::mem::size_of;
+#[cfg(feature = "nightly")]
+use std::sync::{Mutex, MutexGuard, Once};
+
+// the global valloc is shared by every test so only one test may use it at a time
+#[cfg(feature = "nightly")]
+fn lock_global() -> MutexGuard<'static, ()> {
+    static INIT: Once = Once::new();
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    INIT.call_once(|| valloc_init(4096));
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+thread_local! {
+    static HEAP_ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+// counts the heap allocations of every thread, so a test can check that some code doesn't allocate
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = HEAP_ALLOCS.try_with(|x| x.set(x.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static COUNTING: CountingAlloc = CountingAlloc;
+
+fn heap_allocs() -> usize {
+    HEAP_ALLOCS.with(Cell::get)
+}
 
 #[test]
+#[cfg(feature = "nightly")]
 fn custom_vec() {
     // init the global valloc
-    valloc_init(1024);
+    let _guard = lock_global();
     let mut test = Vec::new_in(global_allocator());
     
 
@@ -17,6 +64,21 @@ fn custom_vec() {
     assert_eq!(test[2], 9);
 }
 
+#[test]
+#[cfg(feature = "nightly")]
+fn vec_in_grows() {
+    let _guard = lock_global();
+    let mut small = crate::vec_with_capacity_in::<u32>(2);
+    assert!(small.capacity() >= 2);
+
+    let mut grown = crate::vec_in::<u32>();
+    // well past the first few reallocations
+    for i in 0..100 { grown.push(i * 3); }
+    small.extend_from_slice(&grown[..2]);
+    assert!((0..100).all(|i| grown[i as usize] == i * 3));
+    assert_eq!(small, [0, 3]);
+}
+
 #[test]
 fn alloc_clousre() {
     let mut allocator = Valloc::new(vec![0; 1024].leak());
@@ -145,7 +207,7 @@ fn realloc_fail() {
     assert_eq!(ptr[0], 1);
 
     // there will be an error here so we never allocate the new memory
-    let ptr = allocator.realloc(ptr, 0);
+    let ptr = allocator.realloc(ptr, 2048);
     assert!(ptr.is_err());
 }
 
@@ -197,14 +259,16 @@ fn alloc_array_chars() {
     let mut allocator = Valloc::new(vec![0; 1024].leak());
 
     // Allocate a new String (char array)
-    let ptr = allocator.alloc::<[char]>(13).unwrap();
+    let mut ptr = allocator.alloc_array::<char>(13).unwrap();
 
     const S: &str = "Hello, World!";
 
     // Copy the string into the allocated memory
-    unsafe {
-        std::ptr::copy(S.as_bytes().iter().map(|x| *x as char).collect::<Vec<char>>().as_ptr(), ptr.as_ptr() as *mut char, S.len());
-    }
+    ptr.copy_from_slice(&S.chars().collect::<Vec<char>>()).unwrap();
+    assert_eq!(
+        ptr.copy_from_slice(&"Hello, World!!".chars().collect::<Vec<char>>()),
+        Err(VallocError::SliceTooLong { len: 14, capacity: 13 })
+    );
 
     // compare each character to the string
     for (i, c) in S.chars().enumerate() {
@@ -229,6 +293,72 @@ fn realloc_string() {
     assert_eq!(ptr[1], "Hello, World! times 2 :)");
 }
 
+#[test]
+fn realloc_slice_updates_len() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct TestStruct {
+        a: u8,
+        b: u16,
+        c: u32,
+    }
+
+    let mut ptr = allocator.alloc_array::<TestStruct>(2).unwrap();
+    ptr[0] = TestStruct { a: 1, b: 2, c: 3 };
+    ptr[1] = TestStruct { a: 4, b: 5, c: 6 };
+
+    // the length is counted in elements, not in bytes
+    let mut ptr = allocator.realloc(ptr, size_of::<TestStruct>() * 5).unwrap();
+    assert_eq!(ptr.len(), 5);
+    ptr[4] = TestStruct { a: 7, b: 8, c: 9 };
+    assert_eq!(ptr[0], TestStruct { a: 1, b: 2, c: 3 });
+    assert_eq!(ptr[1], TestStruct { a: 4, b: 5, c: 6 });
+
+    let ptr = allocator.realloc(ptr, size_of::<TestStruct>()).unwrap();
+    assert_eq!(ptr.len(), 1);
+    assert_eq!(ptr[0], TestStruct { a: 1, b: 2, c: 3 });
+
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+fn realloc_moves_to_an_aligned_chunk() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let base = allocator.memory().as_ptr() as usize;
+
+    // the array sits behind a padding gap that is filled right away, and can't grow in place
+    let _pad = allocator.alloc::<u8>(Valloc::align_offset(base, 8) + 1).unwrap();
+    let mut array = allocator.alloc_array::<u64>(2).unwrap();
+    let _fill = allocator.alloc::<u8>(7).unwrap();
+    let _block = allocator.alloc::<u8>(1).unwrap();
+    array[0] = 1;
+    array[1] = 2;
+
+    // the first free byte is at an odd offset, the moved array must not start there
+    let array = allocator.realloc(array, 64).unwrap();
+    assert_eq!(array.as_ptr() as *mut u8 as usize % std::mem::align_of::<u64>(), 0);
+    assert_eq!(array.len(), 8);
+    assert_eq!((array[0], array[1]), (1, 2));
+    assert!(allocator.validate_all().is_empty());
+}
+
+#[test]
+fn realloc_sized_keeps_value() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut ptr = allocator.alloc::<String>(size_of::<String>()).unwrap();
+    *ptr = "Hello, World!".to_string();
+
+    // a sized pointer has no length to update, the String itself must be untouched
+    let mut ptr = allocator.realloc(ptr, size_of::<String>() * 4).unwrap();
+    assert_eq!(*ptr, "Hello, World!");
+    ptr.push_str(" again");
+    assert_eq!(*ptr, "Hello, World! again");
+
+    unsafe { std::ptr::drop_in_place(ptr.as_ptr()) };
+    allocator.free(ptr).unwrap();
+}
+
 #[test]
 fn single_ptr_stress_test() {
     let mut allocator = Valloc::new(vec![0; 1024].leak());
@@ -260,3 +390,2501 @@ fn many_ptr_stress_test() {
         allocator.free(ptr).unwrap();
     }
 }
+
+#[test]
+fn alloc_array_aligned() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    // throw off the alignment of the next free chunk
+    let byte = allocator.alloc::<u8>(1).unwrap();
+
+    let mut ptr = allocator.alloc_array::<u64>(4).unwrap();
+    assert_eq!((ptr.as_ptr() as *mut u64 as usize) % std::mem::align_of::<u64>(), 0);
+    assert_eq!(ptr.len(), 4);
+
+    for i in 0..4 {
+        ptr[i] = i as u64;
+    }
+    for i in 0..4 {
+        assert_eq!(ptr[i], i as u64);
+    }
+
+    allocator.free(ptr).unwrap();
+    allocator.free(byte).unwrap();
+}
+
+#[test]
+fn coalesce_on_free_toggle() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    allocator.set_coalesce_on_free(false);
+
+    let a = allocator.alloc::<u8>(100).unwrap();
+    let b = allocator.alloc::<u8>(100).unwrap();
+    let c = allocator.alloc::<u8>(100).unwrap();
+
+    allocator.free(b).unwrap();
+    allocator.free(a).unwrap();
+
+    // a, b and the free tail all stay separate
+    assert_eq!(allocator.chunks().iter().filter(|x| !x.is_in_use()).count(), 3);
+    assert_eq!(allocator.chunks().get_available(), 1024 - 100);
+
+    // a and b merge, the tail is cut off from them by c
+    assert_eq!(allocator.compact_free_list(), 1);
+    assert_eq!(allocator.chunks().iter().filter(|x| !x.is_in_use()).count(), 2);
+    assert!(allocator.chunks().iter().any(|x| !x.is_in_use() && x.get_size() == 200));
+    assert_eq!(allocator.chunks().get_available(), 1024 - 100);
+
+    allocator.free(c).unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "mmap"))]
+fn mmap_backed_large_arena() {
+    // 1GB of address space, only the touched pages get committed
+    let mut allocator = Valloc::with_backing_from_mmap(1 << 30).unwrap();
+
+    let mut first = allocator.alloc::<u64>(size_of::<u64>()).unwrap();
+    let mut big = allocator.alloc_array::<u8>(1 << 20).unwrap();
+    *first = 42;
+    big[0] = 1;
+    big[(1 << 20) - 1] = 2;
+
+    assert_eq!(*first, 42);
+    assert_eq!(big[0], 1);
+    assert_eq!(big[(1 << 20) - 1], 2);
+
+    allocator.free(big).unwrap();
+    allocator.free(first).unwrap();
+    drop(allocator);
+}
+
+#[test]
+fn realloc_interior_pointer() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let ptr = allocator.alloc::<u8>(16).unwrap();
+    let interior = unsafe { SmartPointer::new_unchecked(ptr.as_ptr().add(4)) };
+    let addr = interior.as_ptr() as usize;
+
+    let available = allocator.chunks().get_available();
+    assert_eq!(allocator.realloc(interior, 32).err().map(|e| e.kind), Some(VallocError::NotAllocated { addr }));
+    // nothing was allocated for the new block
+    assert_eq!(allocator.chunks().get_available(), available);
+
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn unsync_global_loop() {
+    let _guard = lock_global();
+    // the lock keeps every other test away from the global allocator
+    let allocator = unsafe { global_allocator_unsync() };
+
+    for i in 0..1000u32 {
+        let mut test = Vec::with_capacity_in(4, allocator);
+        test.push(i);
+        test.push(i + 1);
+        assert_eq!(test[0] + 1, test[1]);
+    }
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+#[cfg(debug_assertions)]
+fn unsync_global_second_thread() {
+    let _guard = lock_global();
+    // breaks the contract on purpose, the debug check has to catch it before the allocator is touched
+    let allocator = unsafe { global_allocator_unsync() };
+
+    let result = std::thread::spawn(move || {
+        let test: Vec<u8, _> = Vec::with_capacity_in(4, allocator);
+        test.len()
+    }).join();
+    assert!(result.is_err());
+}
+
+#[test]
+fn stats_delta_burst() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let warmup = allocator.alloc::<u8>(64).unwrap();
+    allocator.free(warmup).unwrap();
+
+    let before = allocator.stats();
+    let ptrs: Vec<_> = (0..4).map(|_| allocator.alloc::<u8>(32).unwrap()).collect();
+    let after = allocator.stats();
+
+    let delta = after.delta(&before);
+    assert_eq!(delta.used, 4 * 32);
+    assert_eq!(delta.allocated, 4 * 32);
+    assert_eq!(delta.freed, 0);
+    assert_eq!(delta.peak_increase, 4 * 32 - 64);
+
+    for ptr in ptrs {
+        allocator.free(ptr).unwrap();
+    }
+    let delta = allocator.stats().delta(&after);
+    assert_eq!(delta.used, -(4 * 32));
+    assert_eq!(delta.freed, 4 * 32);
+}
+
+#[test]
+fn stats_delta_swapped_snapshots() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let before = allocator.stats();
+    let ptr = allocator.alloc::<u8>(100).unwrap();
+    allocator.free(ptr).unwrap();
+    let _ = allocator.alloc::<u8>(50).unwrap();
+    let after = allocator.stats();
+
+    // the totals saturate instead of overflowing, the used bytes are signed
+    let delta = before.delta(&after);
+    assert_eq!(delta.allocated, 0);
+    assert_eq!(delta.freed, 0);
+    assert_eq!(delta.peak_increase, 0);
+    assert_eq!(delta.used, -50);
+}
+
+#[test]
+fn backing_view() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let pad = allocator.alloc::<u8>(100).unwrap();
+    let mut ptr = allocator.alloc::<u32>(size_of::<u32>()).unwrap();
+    *ptr = 0xDEADBEEF;
+
+    let offset = ptr.as_ptr() as usize - allocator.backing().as_ptr() as usize;
+    assert_eq!(offset, 100);
+    assert_eq!(allocator.backing()[offset..offset + 4], 0xDEADBEEFu32.to_ne_bytes());
+    assert_eq!(allocator.backing().len(), 1024);
+
+    allocator.free(ptr).unwrap();
+    allocator.free(pad).unwrap();
+}
+
+#[test]
+fn realloc_repeated_growth() {
+    let mut allocator = Valloc::new(vec![0; 4096].leak());
+
+    let mut ptr = allocator.alloc::<[u8]>(1).unwrap();
+    ptr[0] = 0;
+    // keep the chunk from growing in place at first
+    let blocker = allocator.alloc::<u8>(1).unwrap();
+
+    for size in 2..=1000 {
+        ptr = allocator.realloc(ptr, size).unwrap();
+        ptr[size - 1] = size as u8;
+    }
+
+    assert!(allocator.relocations() <= 10, "relocated {} times", allocator.relocations());
+    assert_eq!(ptr[0], 0);
+    for size in 2..=1000 {
+        assert_eq!(ptr[size - 1], size as u8);
+    }
+
+    allocator.free(ptr).unwrap();
+    allocator.free(blocker).unwrap();
+}
+
+#[test]
+fn new_from_static() {
+    // stands in for a `static mut BUF: [u8; 256]`
+    let buf: &'static mut [u8] = Box::leak(Box::new([0u8; 256]));
+    let mut allocator: Valloc<'static> = Valloc::new_from_static(buf);
+
+    let mut ptr = allocator.alloc::<u32>(size_of::<u32>()).unwrap();
+    *ptr = 7;
+    assert_eq!(*ptr, 7);
+    assert_eq!(allocator.chunks().get_available(), 256 - size_of::<u32>());
+
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "overlaps a chunk in use [0..200]")]
+fn alloc_overlap_check() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let _ptr = allocator.alloc::<u8>(100).unwrap();
+    // make the live chunk claim the start of the free chunk after it
+    allocator.chunks_mut().iter_mut()
+        .find(|x| x.is_in_use())
+        .unwrap()
+        .set_size(200);
+
+    let _ = allocator.alloc::<u8>(10);
+}
+
+#[test]
+fn ptr_write_read() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut ptr = allocator.alloc::<u32>(size_of::<u32>() * 4).unwrap();
+    ptr.write(1);
+    assert_eq!(ptr.read(), 1);
+
+    for i in 1..4 {
+        ptr.write_at(i, i as u32 * 10);
+    }
+    assert_eq!(ptr.read(), 1);
+    for i in 1..4 {
+        assert_eq!(ptr.read_at(i), i as u32 * 10);
+    }
+
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+fn array_write_read() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut ptr = allocator.alloc_array::<u16>(3).unwrap();
+    ptr.write(5);
+    ptr.write_at(2, 7);
+    assert_eq!(ptr.read(), 5);
+    assert_eq!(ptr.read_at(2), 7);
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ptr.read_at(3)));
+    assert!(result.is_err());
+
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+fn oldest_live_allocation() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    assert_eq!(allocator.oldest_live(), None);
+
+    let a = allocator.alloc::<u8>(10).unwrap();
+    let b = allocator.alloc::<u8>(20).unwrap();
+    let c = allocator.alloc::<u8>(30).unwrap();
+
+    assert_eq!(allocator.oldest_live(), Some((a.as_ptr(), 10)));
+
+    allocator.free(a).unwrap();
+    assert_eq!(allocator.oldest_live(), Some((b.as_ptr(), 20)));
+
+    // a newer allocation reusing the old space is still the youngest
+    let d = allocator.alloc::<u8>(10).unwrap();
+    assert_eq!(allocator.oldest_live(), Some((b.as_ptr(), 20)));
+
+    allocator.free(b).unwrap();
+    assert_eq!(allocator.oldest_live(), Some((c.as_ptr(), 30)));
+
+    allocator.free(c).unwrap();
+    allocator.free(d).unwrap();
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn grow_zeroed_in_place() {
+    use std::alloc::{Allocator, Layout};
+
+    let mut global = GlobalValloc::new(Valloc::new(vec![0; 1024].leak()));
+    let allocator = &mut global;
+
+    // dirty the memory we will grow into
+    let dirty = allocator.allocate(Layout::array::<u8>(64).unwrap()).unwrap();
+    unsafe { (dirty.as_ptr() as *mut u8).write_bytes(0xFF, 64); }
+    unsafe { allocator.deallocate(dirty.cast(), Layout::array::<u8>(64).unwrap()); }
+
+    let old_layout = Layout::array::<u8>(16).unwrap();
+    let new_layout = Layout::array::<u8>(64).unwrap();
+    let ptr = allocator.allocate_zeroed(old_layout).unwrap();
+    assert_eq!(ptr.as_ptr() as *mut u8, dirty.as_ptr() as *mut u8);
+    unsafe { (ptr.as_ptr() as *mut u8).write_bytes(1, 16); }
+
+    let grown = unsafe { allocator.grow_zeroed(ptr.cast(), old_layout, new_layout) }.unwrap();
+    assert_eq!(grown.as_ptr() as *mut u8, ptr.as_ptr() as *mut u8);
+    assert_eq!(grown.len(), 64);
+
+    let bytes = unsafe { std::slice::from_raw_parts(grown.as_ptr() as *const u8, 64) };
+    assert!(bytes[..16].iter().all(|x| *x == 1));
+    assert!(bytes[16..].iter().all(|x| *x == 0));
+
+    unsafe { allocator.deallocate(grown.cast(), new_layout); }
+}
+
+#[test]
+fn align_offset() {
+    // already aligned
+    assert_eq!(Valloc::align_offset(0, 8), 0);
+    assert_eq!(Valloc::align_offset(64, 16), 0);
+    assert_eq!(Valloc::align_offset(7, 1), 0);
+    // one off
+    assert_eq!(Valloc::align_offset(1, 8), 7);
+    assert_eq!(Valloc::align_offset(15, 16), 1);
+    // large alignments
+    assert_eq!(Valloc::align_offset(4097, 4096), 4095);
+    assert_eq!(Valloc::align_offset(1 << 20, 1 << 21), 1 << 20);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "power of two")]
+fn align_offset_not_power_of_two() {
+    Valloc::align_offset(5, 3);
+}
+
+#[test]
+fn named_allocator_errors() {
+    let mut audio = Valloc::new(vec![0; 64].leak()).with_name("audio");
+    let mut physics = Valloc::new(vec![0; 64].leak());
+    physics.set_name("physics");
+
+    // errors don't carry the name, only the report does
+    assert_eq!(audio.alloc::<u8>(128).err(), Some(VallocError::OutOfMemory { requested: 128, capacity: 64 }));
+    assert_eq!(physics.name(), Some("physics"));
+
+    assert!(audio.report().contains("audio"));
+    assert!(Valloc::new(vec![0; 64].leak()).report().contains("<unnamed>"));
+}
+
+#[test]
+fn compact_preserving_pins() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut ptrs: Vec<_> = (0..7).map(|i| {
+        let mut ptr = allocator.alloc::<u8>(32).unwrap();
+        unsafe { ptr.as_ptr().write_bytes(i as u8, 32); }
+        ptr.write(i as u8);
+        ptr
+    }).collect();
+    let base = ptrs[0].as_ptr();
+
+    // leave holes at 0, 64 and 160
+    let g = ptrs.pop().unwrap();
+    let f = ptrs.pop().unwrap();
+    let e = ptrs.pop().unwrap();
+    let d = ptrs.pop().unwrap();
+    let c = ptrs.pop().unwrap();
+    let b = ptrs.pop().unwrap();
+    let a = ptrs.pop().unwrap();
+    let (b_addr, e_addr) = (b.as_ptr(), e.as_ptr());
+    allocator.free(a).unwrap();
+    allocator.free(c).unwrap();
+    allocator.free(f).unwrap();
+
+    let available = allocator.chunks().get_available();
+    let remap = allocator.compact_preserving(&[&b, &e]);
+
+    // only d and g move, each into the hole right before it
+    assert_eq!(remap, vec![
+        (d.as_ptr(), base.wrapping_add(64)),
+        (g.as_ptr(), base.wrapping_add(160)),
+    ]);
+    assert_eq!(b.as_ptr(), b_addr);
+    assert_eq!(e.as_ptr(), e_addr);
+    assert_eq!(b.read(), 1);
+    assert_eq!(e.read(), 4);
+
+    let d = unsafe { SmartPointer::new_unchecked(remap[0].1) };
+    let g = unsafe { SmartPointer::new_unchecked(remap[1].1) };
+    let d_bytes = unsafe { std::slice::from_raw_parts(d.as_ptr(), 32) };
+    let g_bytes = unsafe { std::slice::from_raw_parts(g.as_ptr(), 32) };
+    assert!(d_bytes.iter().all(|x| *x == 3));
+    assert!(g_bytes.iter().all(|x| *x == 6));
+
+    // nothing was lost, the only free chunks left are in front of the pins and at the end
+    assert_eq!(allocator.chunks().get_available(), available);
+    assert_eq!(allocator.chunks().iter().filter(|x| !x.is_in_use()).count(), 3);
+
+    for ptr in [b, d, e, g] {
+        allocator.free(ptr).unwrap();
+    }
+}
+
+#[test]
+fn ffi_cross_allocator_free() {
+    use crate::ffi::{free_valloc, new_valloc, virtual_alloc, virtual_free, virtual_realloc};
+
+    // the C side only ever holds raw pointers to its allocators
+    let (mem_a, mem_b) = (vec![0u8; 256].leak(), vec![0u8; 256].leak());
+    let a = Box::into_raw(new_valloc(mem_a.as_mut_ptr().cast(), mem_a.len()));
+    let b = Box::into_raw(new_valloc(mem_b.as_mut_ptr().cast(), mem_b.len()));
+
+    let from_a = virtual_alloc(unsafe { &mut *a }, 16);
+    let from_b = virtual_alloc(unsafe { &mut *b }, 16);
+    let b_available = unsafe { &*b }.chunks().get_available();
+
+    // b refuses a pointer it never handed out
+    assert!(!virtual_free(unsafe { &mut *b }, from_a));
+    assert!(virtual_realloc(unsafe { &mut *b }, from_a, 32).is_null());
+    assert_eq!(unsafe { &*b }.chunks().get_available(), b_available);
+
+    assert!(virtual_free(unsafe { &mut *a }, from_a));
+    assert!(virtual_free(unsafe { &mut *b }, from_b));
+    assert_eq!(unsafe { &*b }.chunks().get_available(), 256);
+
+    free_valloc(unsafe { Box::from_raw(a) });
+    free_valloc(unsafe { Box::from_raw(b) });
+}
+
+#[test]
+fn free_run_at() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    allocator.set_coalesce_on_free(false);
+
+    let a = allocator.alloc::<u8>(100).unwrap();
+    let b = allocator.alloc::<u8>(50).unwrap();
+    let c = allocator.alloc::<u8>(50).unwrap();
+    let d = allocator.alloc::<u8>(100).unwrap();
+
+    // b and c are separate free chunks but one run
+    allocator.free(b).unwrap();
+    allocator.free(c).unwrap();
+    assert_eq!(allocator.free_run_at(100), 100);
+    assert_eq!(allocator.free_run_at(160), 40);
+
+    // in use and out of range
+    assert_eq!(allocator.free_run_at(0), 0);
+    assert_eq!(allocator.free_run_at(250), 0);
+    assert_eq!(allocator.free_run_at(300), 1024 - 300);
+    assert_eq!(allocator.free_run_at(2048), 0);
+
+    allocator.free(a).unwrap();
+    allocator.free(d).unwrap();
+}
+
+#[test]
+fn realloc_array_shrink() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut ptr = allocator.alloc_array::<u32>(8).unwrap();
+    let blocker = allocator.alloc::<u8>(1).unwrap();
+    for i in 0..8 {
+        ptr[i] = i as u32;
+    }
+    let (base, available) = (ptr.as_ptr() as *mut u32, allocator.chunks().get_available());
+
+    let ptr = allocator.realloc_array(ptr, 3).unwrap();
+    assert_eq!(ptr.len(), 3);
+    assert_eq!(ptr.as_ptr() as *mut u32, base);
+    assert_eq!(&*ptr, &[0, 1, 2]);
+
+    // the 5 dropped elements are free again and can be handed out
+    assert_eq!(allocator.chunks().get_available(), available + 5 * size_of::<u32>());
+    let tail = allocator.alloc::<u8>(5 * size_of::<u32>()).unwrap();
+    assert_eq!(tail.as_ptr(), base.wrapping_add(3) as *mut u8);
+
+    // growing again has to move past the tail and keeps the elements
+    let ptr = allocator.realloc_array(ptr, 6).unwrap();
+    assert_eq!(ptr.len(), 6);
+    assert_eq!(&ptr[..3], &[0, 1, 2]);
+    assert_eq!((ptr.as_ptr() as *mut u32 as usize) % std::mem::align_of::<u32>(), 0);
+
+    allocator.free(ptr).unwrap();
+    allocator.free(tail).unwrap();
+    allocator.free(blocker).unwrap();
+}
+
+#[test]
+fn realloc_array_shrink_partial_page() {
+    let mut allocator = Valloc::with_page_size(vec![0; 256].leak(), 64);
+
+    // a chunk that isn't a whole number of pages, rounding the new size up must not make it bigger
+    let ptr = allocator.mark_in_use(0, 10).unwrap();
+    let ptr = SmartPointer::new(std::ptr::NonNull::slice_from_raw_parts(ptr.non_null_ptr(), 10));
+    let ptr = allocator.realloc_array(ptr, 5).unwrap();
+    assert_eq!(ptr.len(), 5);
+    assert_eq!(allocator.usable_size(&ptr), Ok(10));
+    assert!(allocator.validate_all().is_empty());
+    allocator.free(ptr).unwrap();
+}
+
+// the core api has to keep building without the `nightly` feature on a stable compiler
+#[test]
+fn core_api_on_stable() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut ptr = allocator.alloc_array::<u32>(4).unwrap();
+    ptr.write_at(3, 3);
+    let ptr = allocator.realloc_array(ptr, 8).unwrap();
+    assert_eq!(ptr.read_at(3), 3);
+    allocator.free(ptr).unwrap();
+
+    assert_eq!(allocator.stats().used, 0);
+}
+
+#[test]
+fn alloc_with_large_struct() {
+    let mut allocator = Valloc::new(vec![0; 8192].leak());
+
+    struct Big {
+        header: u64,
+        data: [u32; 1024],
+    }
+
+    let ptr = allocator.alloc_with(|| Big { header: 0xABCD, data: [7; 1024] }).unwrap();
+    assert_eq!(ptr.header, 0xABCD);
+    assert_eq!(ptr.data[1023], 7);
+    assert_eq!((ptr.as_ptr() as usize) % std::mem::align_of::<Big>(), 0);
+
+    // nothing is built when there is no room for it
+    let mut called = false;
+    assert!(allocator.alloc_with(|| { called = true; Big { header: 0, data: [0; 1024] } }).is_err());
+    assert!(!called);
+
+    // a zero sized value has no chunk to live in, just like an `alloc` of 0 bytes
+    let mut called = false;
+    assert_eq!(allocator.alloc_with(|| { called = true; }).err(), Some(VallocError::ZeroSize));
+    assert!(!called);
+
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+fn split_chunk() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let ptr = allocator.alloc::<u8>(100).unwrap();
+    let base = ptr.as_ptr();
+    let (first, second) = allocator.split_chunk(ptr, 40).unwrap();
+    assert_eq!(first.as_ptr(), base);
+    assert_eq!(second.as_ptr(), base.wrapping_add(40));
+    assert_eq!(allocator.find_chunk(&first).unwrap().get_size(), 40);
+    assert_eq!(allocator.find_chunk(&second).unwrap().get_size(), 60);
+    assert_eq!(allocator.chunks().get_available(), 924);
+
+    // each half is freed on its own
+    allocator.free(first).unwrap();
+    assert_eq!(allocator.chunks().get_available(), 964);
+    allocator.free(second).unwrap();
+    assert_eq!(allocator.chunks().get_available(), 1024);
+
+    // the split has to be strictly inside of the chunk
+    let ptr = allocator.alloc::<u8>(100).unwrap();
+    let copy = SmartPointer::new(ptr.non_null_ptr());
+    assert_eq!(allocator.split_chunk(copy, 0).err(), Some(VallocError::InvalidSplit { at: 0, size: 100 }));
+    let copy = SmartPointer::new(ptr.non_null_ptr());
+    assert_eq!(allocator.split_chunk(copy, 100).err(), Some(VallocError::InvalidSplit { at: 100, size: 100 }));
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+fn split_chunk_fails_without_side_effects() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let ptr = allocator.alloc::<u8>(100).unwrap();
+    let count = allocator.allocation_count();
+
+    // a split that can't happen doesn't use up an allocation id
+    let copy = SmartPointer::new(ptr.non_null_ptr());
+    assert!(allocator.split_chunk(copy, 100).is_err());
+    assert_eq!(allocator.allocation_count(), count);
+
+    allocator.freeze();
+    let copy = SmartPointer::new(ptr.non_null_ptr());
+    assert_eq!(allocator.split_chunk(copy, 40).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.find_chunk(&ptr).unwrap().get_size(), 100);
+    assert_eq!(allocator.allocation_count(), count);
+    allocator.thaw();
+
+    let (first, second) = allocator.split_chunk(ptr, 40).unwrap();
+    assert_eq!(allocator.allocation_count(), count + 1);
+    assert_eq!(allocator.find_chunk(&second).unwrap().get_allocation_id(), count + 1);
+    allocator.free(first).unwrap();
+    allocator.free(second).unwrap();
+}
+
+#[test]
+fn join_chunks() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let ptr = allocator.alloc::<u8>(100).unwrap();
+    let base = ptr.as_ptr();
+    let chunks = allocator.chunks().iter().count();
+    let (first, second) = allocator.split_chunk(ptr, 40).unwrap();
+
+    // the halves have to be passed in memory order
+    let (a, b) = (SmartPointer::new(second.non_null_ptr()), SmartPointer::new(first.non_null_ptr()));
+    assert!(matches!(allocator.join_chunks(a, b), Err(VallocError::NotAdjacent { .. })));
+
+    allocator.freeze();
+    let (a, b) = (SmartPointer::new(first.non_null_ptr()), SmartPointer::new(second.non_null_ptr()));
+    assert_eq!(allocator.join_chunks(a, b).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.find_chunk(&first).unwrap().get_size(), 40);
+    allocator.thaw();
+
+    let joined = allocator.join_chunks(first, second).unwrap();
+    assert_eq!(joined.as_ptr(), base);
+    assert_eq!(allocator.find_chunk(&joined).unwrap().get_size(), 100);
+    assert_eq!(allocator.chunks().iter().count(), chunks);
+    assert_eq!(allocator.chunks().get_available(), 924);
+
+    allocator.free(joined).unwrap();
+    assert_eq!(allocator.chunks().get_available(), 1024);
+}
+
+#[test]
+fn validate_all_reports_every_issue() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let a = allocator.alloc::<u8>(100).unwrap();
+    let b = allocator.alloc::<u8>(100).unwrap();
+    assert!(allocator.validate_all().is_empty());
+    allocator.assert_invariants();
+
+    // cut the first chunk short and lose track of the free space
+    allocator.chunks_mut().iter_mut().next().unwrap().set_size(90);
+    allocator.chunks_mut().set_available(0);
+
+    let issues = allocator.validate_all();
+    assert_eq!(issues.len(), 2);
+    assert!(issues.contains(&ConsistencyIssue::Gap { at: 90, size: 10 }));
+    assert!(issues.contains(&ConsistencyIssue::AvailableMismatch { tracked: 0, actual: 824 }));
+
+    // put it back together
+    allocator.chunks_mut().iter_mut().next().unwrap().set_size(100);
+    allocator.chunks_mut().set_available(824);
+    // both merge back into a single free chunk
+    allocator.free(b).unwrap();
+    allocator.free(a).unwrap();
+    allocator.assert_invariants();
+}
+
+#[test]
+fn validate_all_reports_corrupt_chunks() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let _a = allocator.alloc::<u8>(100).unwrap();
+    let base = allocator.memory().as_ptr() as *mut u8;
+
+    // one chunk before the memory and one that runs far past its end
+    allocator.chunks_mut().push_front(Box::new(ChunkNode::new(base.wrapping_sub(16), 8, true)));
+    allocator.chunks_mut().push_back(Box::new(ChunkNode::new(base.wrapping_add(512), usize::MAX, false)));
+
+    let issues = allocator.validate_all();
+    assert!(issues.contains(&ConsistencyIssue::OutOfRange { at: 0usize.wrapping_sub(16), size: 8 }));
+    assert!(issues.contains(&ConsistencyIssue::OutOfRange { at: 512, size: usize::MAX }));
+    assert!(issues.contains(&ConsistencyIssue::AvailableMismatch { tracked: 924, actual: usize::MAX }));
+    // the chunks that are fine still cover the memory
+    assert!(!issues.iter().any(|x| matches!(x, ConsistencyIssue::Gap { .. } | ConsistencyIssue::Overlap { .. })));
+}
+
+#[test]
+fn leak_check() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let freed = allocator.alloc::<u8>(10).unwrap();
+    let leaked = allocator.alloc::<u8>(20).unwrap();
+    allocator.free(freed).unwrap();
+
+    let leaks = allocator.leak_check();
+    assert_eq!(leaks.len(), 1);
+    assert_eq!((leaks[0].ptr, leaks[0].size), (leaked.as_ptr(), 20));
+}
+
+#[test]
+#[cfg(feature = "backtrace")]
+fn leak_check_backtrace() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let _leaked = allocator.alloc::<u8>(20).unwrap();
+
+    let leaks = allocator.leak_check();
+    let backtrace = format!("{:?}", leaks[0].backtrace.as_ref().unwrap());
+    assert!(backtrace.contains("leak_check_backtrace"), "{backtrace}");
+}
+
+#[test]
+fn reserve_contiguous() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    allocator.set_coalesce_on_free(false);
+
+    // every other 128 byte chunk is freed, leaving 512 bytes free but no more than 128 in a row
+    let mut ptrs: Vec<SmartPointer<u8>> = (0..8).map(|_| allocator.alloc::<u8>(128).unwrap()).collect();
+    let mut kept = Vec::new();
+    for (i, ptr) in ptrs.drain(..).enumerate() {
+        if i % 2 == 0 { allocator.free(ptr).unwrap(); } else { kept.push(ptr); }
+    }
+    for (i, ptr) in kept.iter_mut().enumerate() {
+        unsafe { ptr.as_ptr().write(i as u8); }
+    }
+    assert!(allocator.stats().largest_free < 512);
+
+    assert!(allocator.reserve_contiguous(1024).is_err());
+    let remap = allocator.reserve_contiguous(512).unwrap();
+    assert!(!remap.is_empty());
+
+    // fix up the old pointers with the remap
+    for ptr in kept.iter_mut() {
+        if let Some(&(_, new)) = remap.iter().find(|(old, _)| *old == ptr.as_ptr()) {
+            *ptr = SmartPointer::new(std::ptr::NonNull::new(new).unwrap());
+        }
+    }
+    for (i, ptr) in kept.iter().enumerate() {
+        assert_eq!(unsafe { ptr.as_ptr().read() }, i as u8);
+    }
+
+    // it fits now so nothing has to move
+    assert!(allocator.reserve_contiguous(512).unwrap().is_empty());
+    let big = allocator.alloc::<u8>(512).unwrap();
+
+    allocator.free(big).unwrap();
+    for ptr in kept {
+        allocator.free(ptr).unwrap();
+    }
+}
+
+#[test]
+fn array_iter_mut() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut ptr = allocator.alloc_array::<i32>(8).unwrap();
+    for i in 0..8 {
+        ptr.write_at(i, i as i32 - 4);
+    }
+
+    for x in allocator.array_iter_mut(&ptr).unwrap() {
+        *x *= 2;
+    }
+    assert_eq!(&*ptr, &[-8, -6, -4, -2, 0, 2, 4, 6]);
+
+    // a slice pointer that claims more elements than the chunk holds is cut down to the chunk
+    let long = SmartPointer::new(std::ptr::NonNull::slice_from_raw_parts(ptr.non_null_ptr().cast::<i32>(), 100));
+    assert_eq!(allocator.array_iter_mut(&long).unwrap().count(), 8);
+
+    allocator.free(ptr).unwrap();
+    assert!(allocator.array_iter_mut(&long).is_err());
+}
+
+#[test]
+fn free_base_never_allocated() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let base = allocator.memory().as_ptr() as *mut u8;
+
+    // the whole memory is one free chunk that was never handed out
+    let ptr = SmartPointer::new(std::ptr::NonNull::new(base).unwrap());
+    assert_eq!(allocator.free(ptr).err(), Some(VallocError::NotAllocated { addr: base as usize }));
+}
+
+#[test]
+fn free_base_double_free() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let base = allocator.memory().as_ptr() as *mut u8;
+
+    let ptr = allocator.alloc::<u8>(16).unwrap();
+    assert_eq!(ptr.as_ptr(), base);
+    allocator.free(ptr).unwrap();
+
+    let ptr = SmartPointer::new(std::ptr::NonNull::new(base).unwrap());
+    let err = allocator.free(ptr).err().unwrap();
+    assert_eq!(err, VallocError::DoubleFree { addr: base as usize });
+    assert!(err.to_string().contains("already freed"));
+}
+
+#[test]
+fn bytes_between() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let a = allocator.alloc::<u8>(40).unwrap();
+    let b = allocator.alloc::<u64>(8).unwrap();
+    assert_eq!(allocator.bytes_between(&a, &b), Ok(40));
+    assert_eq!(allocator.bytes_between(&b, &a), Ok(-40));
+
+    let mut outside = 0u8;
+    let outside = SmartPointer::new(std::ptr::NonNull::from(&mut outside));
+    assert!(matches!(allocator.bytes_between(&a, &outside), Err(VallocError::OutOfBounds { .. })));
+
+    allocator.free(a).unwrap();
+    allocator.free(b).unwrap();
+}
+
+#[test]
+fn vvec_grows_past_capacity() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    {
+        let mut v = VVec::with_capacity(&mut allocator, 2).unwrap();
+        assert_eq!(v.capacity(), 2);
+        for i in 0..20u32 {
+            v.push(i * 3).unwrap();
+        }
+        assert!(v.capacity() >= 20);
+        assert_eq!(v.len(), 20);
+        assert_eq!(v.get(7), Some(&21));
+        assert_eq!(v.get(20), None);
+
+        assert_eq!(v.pop(), Some(57));
+        assert_eq!(v.as_slice()[..4], [0, 3, 6, 9]);
+    }
+
+    // dropping the vec gives its buffer back
+    assert_eq!(allocator.stats().used, 0);
+}
+
+#[test]
+fn vvec_drops_elements() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let counter = std::rc::Rc::new(());
+
+    let mut v = VVec::new(&mut allocator);
+    for _ in 0..5 {
+        v.push(counter.clone()).unwrap();
+    }
+    drop(v.pop());
+    assert_eq!(std::rc::Rc::strong_count(&counter), 5);
+    drop(v);
+    assert_eq!(std::rc::Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn realloc_failure_returns_original() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+
+    let mut ptr = allocator.alloc::<u8>(64).unwrap();
+    let blocker = allocator.alloc::<u8>(64).unwrap();
+    *ptr = 42;
+
+    // there is no room for 200 bytes, but the old chunk is still ours
+    let Err(err) = allocator.realloc(ptr, 200) else { panic!("200 bytes can't fit!") };
+    assert!(matches!(err.kind, VallocError::NoContiguousSpace { requested: 200, .. }));
+    let mut ptr = err.original;
+    assert_eq!(*ptr, 42);
+    *ptr = 7;
+    assert!(allocator.owns(&ptr));
+
+    allocator.free(ptr).unwrap();
+    allocator.free(blocker).unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "mmap"))]
+fn mmap_prefault() {
+    let mut allocator = Valloc::with_backing_from_mmap(1 << 24).unwrap();
+    let page = crate::mmap::page_size();
+
+    // the range doesn't have to be page aligned and is clamped to the memory
+    allocator.prefault(page / 2, 64 * page);
+    allocator.prefault((1 << 24) - 1, 4 * page);
+
+    let mut big = allocator.alloc_array::<u8>(64 * page).unwrap();
+    big[0] = 1;
+    big[64 * page - 1] = 2;
+    assert_eq!((big[0], big[64 * page - 1]), (1, 2));
+    allocator.free(big).unwrap();
+
+    // borrowed memory is already committed so this does nothing
+    let mut borrowed = Valloc::new(vec![0; 64].leak());
+    borrowed.prefault(0, 64);
+}
+
+#[test]
+fn checksum_tracks_live_bytes() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let mut a = allocator.alloc_array::<u8>(16).unwrap();
+    let b = allocator.alloc_array::<u8>(16).unwrap();
+    a.copy_from_slice(b"sixteen bytes...").unwrap();
+    let before = allocator.checksum();
+    assert_eq!(allocator.checksum(), before);
+
+    a[3] ^= 0xFF;
+    assert_ne!(allocator.checksum(), before);
+    a[3] ^= 0xFF;
+    assert_eq!(allocator.checksum(), before);
+
+    // freeing and allocating the same bytes back restores it
+    allocator.free(b).unwrap();
+    assert_ne!(allocator.checksum(), before);
+    let b = allocator.alloc_array::<u8>(16).unwrap();
+    assert_eq!(allocator.checksum(), before);
+
+    allocator.free(a).unwrap();
+    allocator.free(b).unwrap();
+}
+
+#[test]
+fn free_ignore_missing() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let ptr = allocator.alloc::<u8>(16).unwrap();
+    let again = SmartPointer::new(ptr.non_null_ptr());
+    assert!(allocator.free_ignore_missing(ptr));
+    assert!(!allocator.free_ignore_missing(again));
+    assert_eq!(allocator.chunks().get_available(), 1024);
+
+    let mut outside = 0u8;
+    assert!(!allocator.free_ignore_missing(SmartPointer::new(std::ptr::NonNull::from(&mut outside))));
+}
+
+#[test]
+fn low_memory_watermark() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let calls = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let seen = calls.clone();
+    allocator.set_low_memory_watermark(300, Box::new(move |available| seen.borrow_mut().push(available)));
+
+    let a = allocator.alloc::<u8>(500).unwrap();
+    assert!(calls.borrow().is_empty());
+    let b = allocator.alloc::<u8>(300).unwrap();
+    let c = allocator.alloc::<u8>(100).unwrap();
+    // only the crossing counts, staying below doesn't fire again
+    assert_eq!(*calls.borrow(), vec![224]);
+
+    // going back above the watermark arms it again
+    allocator.free(c).unwrap();
+    allocator.free(b).unwrap();
+    let b = allocator.alloc::<u8>(10).unwrap();
+    let c = allocator.alloc::<u8>(400).unwrap();
+    assert_eq!(*calls.borrow(), vec![224, 114]);
+
+    for ptr in [a, b, c] {
+        allocator.free(ptr).unwrap();
+    }
+}
+
+#[test]
+fn defragment_overlapping_slide() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    // a 16 byte gap followed by a 512 byte chunk, sliding it down overlaps itself
+    let gap = allocator.alloc::<u8>(16).unwrap();
+    let mut big = allocator.alloc_array::<u8>(512).unwrap();
+    for (i, byte) in big.iter_mut().enumerate() {
+        *byte = (i * 7 % 251) as u8;
+    }
+    allocator.free(gap).unwrap();
+
+    let remap = allocator.defragment();
+    assert_eq!(remap.len(), 1);
+    let (old, new) = remap[0];
+    assert_eq!(old, big.as_ptr() as *mut u8);
+    assert_eq!(new, allocator.memory().as_ptr() as *mut u8);
+
+    let moved = unsafe { std::slice::from_raw_parts(new, 512) };
+    assert!(moved.iter().enumerate().all(|(i, &byte)| byte == (i * 7 % 251) as u8));
+    assert_eq!(allocator.stats().largest_free, 512);
+    allocator.assert_invariants();
+}
+
+#[test]
+fn raw_parts_round_trip() {
+    let memory: &'static mut [u8] = vec![0; 1024].leak();
+    let (mem, len) = (memory.as_mut_ptr(), memory.len());
+    let mut allocator = Valloc::new(memory);
+
+    let a = allocator.alloc::<u8>(100).unwrap();
+    let b = allocator.alloc::<u8>(50).unwrap();
+    allocator.free(a).unwrap();
+    let parts = allocator.chunk_list_repr();
+    let (stats, b) = (allocator.stats(), b.as_ptr());
+    drop(allocator);
+
+    let mut rebuilt = unsafe { Valloc::try_from_raw_parts(mem, len, parts.clone()) }.unwrap();
+    assert_eq!(rebuilt.chunk_list_repr(), parts);
+    assert_eq!(rebuilt.stats().available, stats.available);
+    rebuilt.free(SmartPointer::new(std::ptr::NonNull::new(b).unwrap())).unwrap();
+    assert_eq!(rebuilt.stats().available, len);
+}
+
+#[test]
+fn raw_parts_rejects_corruption() {
+    let memory: &'static mut [u8] = vec![0; 1024].leak();
+    let (mem, len) = (memory.as_mut_ptr(), memory.len());
+    let chunk = |offset, size, in_use| crate::allocator::ChunkRepr { offset, size, in_use };
+
+    // past the end of the memory
+    let parts = crate::allocator::ChunkListRepr { chunks: vec![chunk(0, 100, true), chunk(100, 1000, false)] };
+    let err = unsafe { Valloc::try_from_raw_parts(mem, len, parts) }.err();
+    assert!(matches!(err, Some(VallocError::OutOfBounds { .. })));
+
+    // overlapping chunks
+    let parts = crate::allocator::ChunkListRepr { chunks: vec![chunk(0, 100, true), chunk(90, 934, false)] };
+    let err = unsafe { Valloc::try_from_raw_parts(mem, len, parts) }.err();
+    assert!(matches!(err, Some(VallocError::Inconsistent { issue: ConsistencyIssue::Overlap { a: 0, b: 90 } })));
+
+    // a hole between chunks
+    let parts = crate::allocator::ChunkListRepr { chunks: vec![chunk(0, 100, true), chunk(110, 914, false)] };
+    let err = unsafe { Valloc::try_from_raw_parts(mem, len, parts) }.err();
+    assert!(matches!(err, Some(VallocError::Inconsistent { issue: ConsistencyIssue::Gap { at: 100, size: 10 } })));
+}
+
+#[test]
+fn ring_streaming_wraps_around() {
+    // big enough for two messages in flight plus the bytes skipped at the end
+    let mut ring = RingValloc::new(vec![0; 160].leak());
+    let base = ring.alloc(1).unwrap().as_ptr() as *mut u8;
+    ring.free_oldest(1).unwrap();
+
+    // stream messages of different sizes through the ring, keeping at most 3 in flight
+    let mut in_flight = std::collections::VecDeque::new();
+    let mut wrapped = 0;
+    for i in 0..200usize {
+        let size = 10 + i % 25;
+        if in_flight.len() == 3 {
+            let (oldest, len, fill): (SmartPointer<[u8]>, usize, u8) = in_flight.pop_front().unwrap();
+            assert!(oldest.iter().all(|&byte| byte == fill));
+            ring.free_oldest(len).unwrap();
+        }
+        let mut msg = ring.alloc(size).unwrap();
+        if msg.as_ptr() as *mut u8 == base && i > 0 { wrapped += 1; }
+        msg.fill(i as u8);
+        in_flight.push_back((msg, size, i as u8));
+    }
+    assert!(wrapped > 10);
+
+    for (_, len, _) in in_flight {
+        ring.free_oldest(len).unwrap();
+    }
+    assert_eq!(ring.used(), 0);
+}
+
+#[test]
+fn ring_head_cannot_pass_tail() {
+    let mut ring = RingValloc::new(vec![0; 100].leak());
+
+    let _a = ring.alloc(40).unwrap();
+    let _b = ring.alloc(40).unwrap();
+    ring.free_oldest(40).unwrap();
+
+    // 30 bytes don't fit at the end so they go to the front, before the tail at 40
+    let c = ring.alloc(30).unwrap();
+    assert_eq!(c.as_ptr() as *mut u8 as usize, _a.as_ptr() as *mut u8 as usize);
+    assert!(matches!(ring.alloc(20), Err(VallocError::NoContiguousSpace { requested: 20, .. })));
+    assert!(ring.free_oldest(100).is_err());
+
+    ring.free_oldest(40).unwrap();
+    let d = ring.alloc(60).unwrap();
+    assert_eq!(d.as_ptr() as *mut u8 as usize, c.as_ptr() as *mut u8 as usize + 30);
+}
+
+#[test]
+fn guard_region_is_never_handed_out() {
+    let mut allocator = Valloc::with_guard_region(vec![0xAA; 1024].leak(), 64);
+    let guard = allocator.memory().as_ptr() as usize + 1024 - 64;
+    assert_eq!(allocator.chunks().get_available(), 960);
+    allocator.assert_invariants();
+
+    // fill the memory up with allocations of every size
+    let mut ptrs = Vec::new();
+    for size in (1..64).cycle() {
+        match allocator.alloc::<u8>(size) {
+            Ok(ptr) => {
+                assert!((ptr.as_ptr() as usize) + size <= guard);
+                ptrs.push(ptr);
+            },
+            Err(_) => break,
+        }
+    }
+    while let Ok(ptr) = allocator.alloc::<u8>(1) {
+        assert!((ptr.as_ptr() as usize) < guard);
+        ptrs.push(ptr);
+    }
+    assert_eq!(allocator.chunks().get_available(), 0);
+
+    // the guard can't be freed, and it doesn't move on compaction
+    let guard_ptr = SmartPointer::new(std::ptr::NonNull::new(guard as *mut u8).unwrap());
+    assert!(!allocator.owns(&guard_ptr));
+    assert!(allocator.free(guard_ptr).is_err());
+    for ptr in ptrs.drain(..).step_by(2) {
+        allocator.free(ptr).unwrap();
+    }
+    allocator.defragment();
+    assert!(allocator.chunks().iter().any(|x| x.get_ptr::<u8>() as usize == guard && x.is_in_use()));
+
+    // an overrun into the guard is caught
+    unsafe { (guard as *mut u8).add(3).write(1); }
+    assert_eq!(allocator.validate_all(), vec![ConsistencyIssue::GuardOverwritten { at: 1024 - 64 + 3 }]);
+}
+
+#[test]
+fn ffi_vrealloc_inplace() {
+    use crate::ffi::{free_valloc, new_valloc, virtual_alloc, virtual_free, vrealloc_inplace};
+
+    let mem = vec![0u8; 256].leak();
+    let valloc = Box::into_raw(new_valloc(mem.as_mut_ptr().cast(), mem.len()));
+
+    // the chunk after `a` is free so it can grow (and shrink) where it is
+    let a = virtual_alloc(unsafe { &mut *valloc }, 16);
+    assert!(vrealloc_inplace(unsafe { &mut *valloc }, a, 64));
+    assert_eq!(unsafe { &*valloc }.chunks().get_available(), 256 - 64);
+    // shrinking gives the tail back
+    assert!(vrealloc_inplace(unsafe { &mut *valloc }, a, 8));
+    assert_eq!(unsafe { &*valloc }.usable_size(&SmartPointer::new(std::ptr::NonNull::new(a).unwrap())), Ok(8));
+    assert_eq!(unsafe { &*valloc }.chunks().get_available(), 256 - 8);
+    // asking for what it already has changes nothing
+    assert!(vrealloc_inplace(unsafe { &mut *valloc }, a, 8));
+    assert_eq!(unsafe { &*valloc }.chunks().get_available(), 256 - 8);
+
+    // `b` is right after `a` so `a` would have to move
+    let b = virtual_alloc(unsafe { &mut *valloc }, 16);
+    let stats = unsafe { &*valloc }.stats();
+    assert!(!vrealloc_inplace(unsafe { &mut *valloc }, a, 128));
+    assert_eq!(unsafe { &*valloc }.stats(), stats);
+
+    assert!(virtual_free(unsafe { &mut *valloc }, a));
+    assert!(virtual_free(unsafe { &mut *valloc }, b));
+    assert!(!vrealloc_inplace(unsafe { &mut *valloc }, a, 8));
+    free_valloc(unsafe { Box::from_raw(valloc) });
+}
+
+#[test]
+fn usable_size() {
+    use crate::ffi::vmalloc_usable_size;
+
+    // the C side only ever holds a raw pointer to its allocator
+    let valloc = Box::into_raw(Box::new(Valloc::new(vec![0; 1024].leak())));
+    let allocator = unsafe { &mut *valloc };
+
+    let ptr = allocator.alloc::<u8>(100).unwrap();
+    assert_eq!(allocator.usable_size(&ptr), Ok(100));
+
+    // even a small shrink gives the rest of the chunk back
+    let available = allocator.available();
+    let ptr = allocator.realloc(ptr, 60).unwrap();
+    assert_eq!(allocator.usable_size(&ptr), Ok(60));
+    assert_eq!(allocator.available(), available + 40);
+
+    // a moved chunk is over-allocated to make room to grow
+    let blocker = allocator.alloc::<u8>(1).unwrap();
+    let ptr = allocator.realloc(ptr, 61).unwrap();
+    assert_eq!(allocator.usable_size(&ptr), Ok(120));
+    assert_eq!(vmalloc_usable_size(unsafe { &*valloc }, ptr.as_ptr().cast()), 120);
+
+    let addr = ptr.as_ptr();
+    allocator.free(ptr).unwrap();
+    allocator.free(blocker).unwrap();
+    assert_eq!(vmalloc_usable_size(unsafe { &*valloc }, addr.cast()), 0);
+    drop(unsafe { Box::from_raw(valloc) });
+}
+
+#[test]
+fn deterministic_layout() {
+    // the same sequence of allocations, with some frees in between
+    fn run(allocator: &mut Valloc) -> Vec<usize> {
+        let base = allocator.memory().as_ptr() as usize;
+        let mut offsets = Vec::new();
+        let mut live = Vec::new();
+        for i in 1..20usize {
+            let ptr = if i % 3 == 0 {
+                allocator.alloc_array::<u64>(i).unwrap().cast::<u8>()
+            } else {
+                allocator.alloc::<u8>(i * 5).unwrap()
+            };
+            offsets.push(ptr.as_ptr() as usize - base);
+            live.push(ptr);
+            if i % 4 == 0 {
+                allocator.free(live.remove(0)).unwrap();
+            }
+        }
+        offsets
+    }
+
+    let mut allocator = Valloc::new(vec![0; 4096].leak());
+    // some history that would change where a first fit lands
+    let a = allocator.alloc::<u8>(7).unwrap();
+    let b = allocator.alloc::<u8>(300).unwrap();
+    allocator.free(a).unwrap();
+    allocator.free(b).unwrap();
+
+    allocator.set_deterministic(true);
+    let first = run(&mut allocator);
+    allocator.reset();
+    let second = run(&mut allocator);
+    assert_eq!(first, second);
+
+    // freed memory is never handed out again and the offsets only go up
+    assert!(first.windows(2).all(|x| x[0] < x[1]));
+    assert_eq!(first[0], 0);
+    assert_eq!(first[2] % std::mem::align_of::<u64>(), 0);
+
+    let mut fresh = Valloc::new(vec![0; 4096].leak());
+    fresh.set_deterministic(true);
+    assert_eq!(run(&mut fresh), first);
+}
+
+#[test]
+fn deterministic_compaction_moves_the_frontier() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    allocator.set_deterministic(true);
+    let base = allocator.memory().as_ptr() as usize;
+
+    // the freed chunk is bigger than the request but lies behind the frontier
+    let a = allocator.alloc::<u8>(512).unwrap();
+    let b = allocator.alloc::<u8>(400).unwrap();
+    allocator.free(a).unwrap();
+    let remap = allocator.reserve_contiguous(128).unwrap();
+    assert_eq!(remap, vec![(b.as_ptr(), base as *mut u8)]);
+    let c = allocator.alloc::<u8>(128).unwrap();
+    assert_eq!(c.as_ptr() as usize - base, 400);
+
+    // defragment hands the space it reclaimed back to the frontier as well
+    allocator.free(c).unwrap();
+    let d = allocator.alloc::<u8>(100).unwrap();
+    let _e = allocator.alloc::<u8>(100).unwrap();
+    allocator.free(d).unwrap();
+    allocator.defragment();
+    let f = allocator.alloc::<u8>(524).unwrap();
+    assert_eq!(f.as_ptr() as usize - base, 500);
+}
+
+#[test]
+fn realloc_zero_frees() {
+    use crate::ffi::{free_valloc, new_valloc, virtual_alloc, virtual_realloc};
+
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let a = allocator.alloc::<u8>(100).unwrap();
+    let b = allocator.alloc_array::<u32>(4).unwrap();
+
+    // the chunk is freed and what comes back points nowhere near the memory
+    let dangling = allocator.realloc(a, 0).unwrap();
+    assert!(!allocator.owns(&dangling));
+    assert!(!allocator.memory().as_ptr_range().contains(&(dangling.as_ptr() as *const u8)));
+    assert_eq!(allocator.chunks().get_available(), 1024 - 16);
+
+    // fat pointers keep their length but not their address
+    let dangling = allocator.realloc(b, 0).unwrap();
+    assert!(!allocator.owns(&dangling));
+    assert_eq!(allocator.chunks().get_available(), 1024);
+    // the two chunks are merged back together
+    assert!(allocator.validate_all().is_empty());
+
+    // a second realloc to 0 is not a free of whatever is there now
+    let c = allocator.alloc::<u8>(8).unwrap();
+    assert!(allocator.realloc(dangling, 0).is_err());
+    allocator.free(c).unwrap();
+
+    let mem = vec![0u8; 256].leak();
+    let valloc = Box::into_raw(new_valloc(mem.as_mut_ptr().cast(), mem.len()));
+    let ptr = virtual_alloc(unsafe { &mut *valloc }, 16);
+    assert!(virtual_realloc(unsafe { &mut *valloc }, ptr, 0).is_null());
+    assert_eq!(unsafe { &*valloc }.chunks().get_available(), 256);
+    free_valloc(unsafe { Box::from_raw(valloc) });
+}
+
+#[test]
+fn coalesce_stats() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+
+    let a = allocator.alloc::<u8>(100).unwrap();
+    let b = allocator.alloc::<u8>(100).unwrap();
+    let c = allocator.alloc::<u8>(100).unwrap();
+
+    // a has b in use after it, c merges with the free memory after it,
+    // b merges with c and then into a
+    allocator.free(a).unwrap();
+    allocator.free(c).unwrap();
+    allocator.free(b).unwrap();
+
+    let stats = allocator.coalesce_stats();
+    assert_eq!(stats.frees_without_merge, 1);
+    assert_eq!(stats.merges_forward, 2);
+    assert_eq!(stats.merges_backward, 1);
+    assert_eq!(allocator.chunks().iter().count(), 1);
+}
+
+#[test]
+fn page_sized_allocations() {
+    const PAGE: usize = 4096;
+    let mut allocator = Valloc::with_page_size(vec![0; 4 * PAGE].leak(), PAGE);
+
+    let a = allocator.alloc::<u8>(100).unwrap();
+    assert_eq!(allocator.usable_size(&a), Ok(PAGE));
+    assert_eq!(a.as_ptr() as usize % PAGE, 0);
+
+    // arrays and reallocs stay on whole pages too
+    let b = allocator.alloc_array::<u32>(1025).unwrap();
+    assert_eq!(allocator.usable_size(&b), Ok(2 * PAGE));
+    assert_eq!(b.as_ptr() as *mut u8 as usize % PAGE, 0);
+    let b = allocator.realloc_array(b, 10).unwrap();
+    assert_eq!(allocator.usable_size(&b), Ok(PAGE));
+
+    allocator.free(a).unwrap();
+    allocator.free(b).unwrap();
+    assert_eq!(allocator.chunks().get_available(), 4 * PAGE);
+}
+
+#[test]
+fn quick_check() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let a = allocator.alloc::<u8>(100).unwrap();
+    assert!(allocator.quick_check());
+
+    let empty = ChunkNode::new(a.as_ptr(), 0, false);
+    allocator.chunks_mut().push_back(Box::new(empty));
+    assert!(!allocator.quick_check());
+
+    allocator.chunks_mut().pop_back();
+    assert!(allocator.quick_check());
+    allocator.chunks_mut().set_available(0);
+    assert!(!allocator.quick_check());
+}
+
+#[test]
+fn smart_pointer_try_new() {
+    assert!(SmartPointer::<u32>::try_new(std::ptr::null_mut()).is_none());
+
+    let mut value = 5u32;
+    let ptr = SmartPointer::try_new(&mut value as *mut u32).unwrap();
+    assert_eq!(ptr.read(), 5);
+
+    // the sentinel is never handed out by an allocator
+    let dangling = SmartPointer::<u64>::dangling();
+    assert_eq!(dangling.as_ptr() as usize % std::mem::align_of::<u64>(), 0);
+    let allocator = Valloc::new(vec![0; 64].leak());
+    assert!(!allocator.owns(&dangling));
+}
+
+#[test]
+fn alloc_layout_array() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let layout = std::alloc::Layout::array::<u64>(5).unwrap();
+
+    let _pad = allocator.alloc::<u8>(3).unwrap();
+    let ptr = allocator.alloc_layout_array::<u64>(5).unwrap();
+    assert_eq!(ptr.len(), 5);
+    assert_eq!(allocator.usable_size(&ptr), Ok(layout.size()));
+    assert_eq!(ptr.as_ptr() as *mut u8 as usize % layout.align(), 0);
+
+    assert_eq!(allocator.alloc_layout_array::<u64>(usize::MAX / 4).err(), Some(VallocError::CapacityOverflow));
+}
+
+#[test]
+fn freeze_and_thaw() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let mut ptr = allocator.alloc::<u32>(size_of::<u32>()).unwrap();
+    allocator.write(&mut ptr, 7).unwrap();
+
+    allocator.freeze();
+    assert_eq!(allocator.alloc::<u8>(1).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.write(&mut ptr, 8), Err(VallocError::Frozen));
+    assert_eq!(allocator.read(&ptr), Ok(7));
+    let ptr = allocator.realloc(ptr, 64).unwrap_or_else(|e| { assert_eq!(e.kind, VallocError::Frozen); e.original });
+    let ptr = match allocator.free(ptr.cast::<u8>()) {
+        Err(VallocError::Frozen) => ptr,
+        other => panic!("free went through a frozen allocator: {other:?}"),
+    };
+
+    allocator.thaw();
+    let mut ptr = ptr;
+    allocator.write(&mut ptr, 8).unwrap();
+    assert_eq!(allocator.read(&ptr), Ok(8));
+    let stale = ptr.cast::<u32>();
+    allocator.free(ptr).unwrap();
+
+    // reads and writes have to land inside of an allocation
+    assert!(allocator.read(&stale).is_err());
+}
+
+#[test]
+fn frozen_allocator_changes_nothing() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    allocator.set_coalesce_on_free(false);
+    let a = allocator.alloc::<u8>(100).unwrap();
+    let hole = allocator.alloc::<u8>(50).unwrap();
+    let gap = allocator.alloc::<u8>(10).unwrap();
+    let array = allocator.alloc_array::<u64>(4).unwrap();
+    let tagged = allocator.alloc_tagged::<u8>(16, "frozen").unwrap();
+    allocator.free(hole).unwrap();
+    allocator.free(gap).unwrap();
+    let copy = |ptr: &SmartPointer<u8>| SmartPointer::<u8>::new(ptr.non_null_ptr());
+
+    let (chunks, memory) = (allocator.chunk_list_repr(), allocator.backing().to_vec());
+    allocator.freeze();
+
+    assert!(!allocator.grow_in_place(&a, 120));
+    assert_eq!(allocator.shrink_in_place(&a, 10), Err(VallocError::Frozen));
+    assert_eq!(allocator.reserve_contiguous(64).err(), Some(VallocError::Frozen));
+    assert!(allocator.defragment().is_empty());
+    assert!(allocator.compact_preserving(&[&a]).is_empty());
+    let mut live = [copy(&a), copy(&tagged)];
+    allocator.compact_all(&mut live);
+    assert_eq!(live[1].as_ptr(), tagged.as_ptr());
+    assert_eq!(allocator.defragment_budgeted(usize::MAX), (0, false));
+    assert_eq!(allocator.compact_free_list(), 0);
+    assert_eq!(allocator.drain_free_list(), allocator.stats().free_chunks);
+    assert_eq!(allocator.array_iter_mut(&array).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.split_chunk(copy(&a), 10).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.join_chunks(copy(&a), copy(&a)).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.relocate_chunk(copy(&tagged), 0).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.mark_in_use(100, 10).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.mark_free(0, 100), Err(VallocError::Frozen));
+    assert_eq!(allocator.free_by_tag("frozen"), 0);
+    assert!(!allocator.free_ignore_missing(copy(&a)));
+    assert!(allocator.fill::<u8>(8).is_empty());
+    assert_eq!(allocator.realloc_array(array, 8).err(), Some(VallocError::Frozen));
+    allocator.reset();
+
+    assert_eq!(allocator.chunk_list_repr(), chunks);
+    assert_eq!(allocator.backing(), &memory[..]);
+    allocator.thaw();
+    assert!(allocator.grow_in_place(&a, 120));
+}
+
+#[test]
+fn relocate_chunk_into_hole() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let base = allocator.memory().as_ptr() as usize;
+    let hole = allocator.alloc::<[u8]>(64).unwrap();
+    let mut moving = allocator.alloc_array::<u32>(4).unwrap();
+    let live = allocator.alloc::<[u8]>(32).unwrap();
+    allocator.free(hole).unwrap();
+    for i in 0..4 { moving.write_at(i, i as u32 * 11); }
+
+    // the live chunk right after it is in the way
+    let Err(e) = allocator.relocate_chunk(moving.cast::<u8>(), 72) else { panic!("moved over a live chunk") };
+    assert_eq!(e, VallocError::DestinationInUse { at: 80 });
+
+    let moved = allocator.relocate_chunk(moving, 16).unwrap();
+    assert_eq!(moved.as_ptr() as *mut u8 as usize, base + 16);
+    assert_eq!((0..4).map(|i| moved.read_at(i)).collect::<Vec<_>>(), [0, 11, 22, 33]);
+    assert!(allocator.owns(&moved));
+    assert!(allocator.validate_all().is_empty());
+
+    allocator.free(moved).unwrap();
+    allocator.free(live).unwrap();
+}
+
+#[test]
+fn default_is_empty() {
+    let mut allocator = Valloc::default();
+    assert_eq!(allocator.available(), 0);
+    assert_eq!(allocator.alloc::<u8>(1).err(), Some(VallocError::OutOfMemory { requested: 1, capacity: 0 }));
+    assert!(allocator.validate_all().is_empty());
+    assert_eq!(allocator.stats().largest_free, 0);
+}
+
+#[test]
+fn read_write_at_offset() {
+    let mut allocator = Valloc::new(vec![0; 128].leak());
+    allocator.write_at_offset(100, 0xDEAD_BEEFu32).unwrap();
+    assert_eq!(allocator.read_at_offset::<u32>(100), Ok(0xDEAD_BEEF));
+    // unaligned and partly past the end
+    assert_eq!(allocator.read_at_offset::<u8>(101), Ok(0xDEAD_BEEFu32.to_ne_bytes()[1]));
+    assert!(matches!(allocator.write_at_offset(126, 1u32), Err(VallocError::OutOfBounds { .. })));
+    assert!(matches!(allocator.read_at_offset::<u64>(usize::MAX), Err(VallocError::OutOfBounds { .. })));
+}
+
+#[test]
+fn snapshot_diff_roundtrip() {
+    use crate::snapshot::DIFF_BLOCK;
+
+    let mut original = Valloc::new(vec![0; 1024].leak());
+    let mut a = original.alloc_array::<u8>(100).unwrap();
+    let _b = original.alloc_array::<u8>(300).unwrap();
+    let snapshot = original.snapshot();
+
+    // the copy starts out in the state of the snapshot
+    let memory = snapshot.memory.clone().leak();
+    let mut copy = unsafe { Valloc::try_from_raw_parts(memory.as_mut_ptr(), memory.len(), snapshot.chunks.clone()) }.unwrap();
+    assert!(original.diff_since(&snapshot).is_empty());
+
+    a.write_at(10, 0xAA);
+    let c = original.alloc_array::<u8>(16).unwrap();
+    let diff = original.diff_since(&snapshot);
+    assert_eq!(diff.ranges.len(), 1);
+    assert_eq!(diff.changed_bytes(), DIFF_BLOCK);
+    assert!(diff.chunks.is_some());
+
+    copy.apply_diff(&diff).unwrap();
+    assert_eq!(copy.backing(), original.backing());
+    assert_eq!(copy.chunk_list_repr(), original.chunk_list_repr());
+    assert_eq!(copy.checksum(), original.checksum());
+    original.free(c).unwrap();
+}
+
+#[test]
+fn compact_threshold_auto() {
+    let mut allocator = Valloc::new(vec![0; 512].leak());
+    allocator.set_coalesce_on_free(false);
+    allocator.set_compact_threshold_auto(Some(0.4));
+    let ptrs: Vec<SmartPointer<[u8]>> = (0..4).map(|_| allocator.alloc(64).unwrap()).collect();
+    let mut ptrs = ptrs.into_iter();
+
+    // 256 of 384 free bytes are in one piece, still below the threshold
+    allocator.free(ptrs.next().unwrap()).unwrap();
+    allocator.free(ptrs.next().unwrap()).unwrap();
+    assert_eq!(allocator.stats().free_chunks, 3);
+
+    // 256 of 448 crosses it and the first three chunks are merged
+    allocator.free(ptrs.next().unwrap()).unwrap();
+    assert_eq!(allocator.stats().free_chunks, 2);
+    assert!(allocator.fragmentation() > 0.4);
+    allocator.free(ptrs.next().unwrap()).unwrap();
+    assert_eq!(allocator.fragmentation(), 0.0);
+}
+
+#[test]
+fn realloc_aligned_moves_to_alignment() {
+    let mut allocator = Valloc::new(vec![0; 512].leak());
+    // knock the next chunk off of any alignment
+    let _pad = allocator.alloc::<u8>(1).unwrap();
+    let mut ptr = allocator.alloc::<[u8]>(16).unwrap();
+    for i in 0..16 { ptr[i] = i as u8 * 2; }
+    assert_ne!(ptr.as_ptr() as *mut u8 as usize % 32, 0);
+
+    let ptr = allocator.realloc_aligned(ptr, 64, 32).unwrap();
+    assert_eq!(ptr.as_ptr() as *mut u8 as usize % 32, 0);
+    assert!((0..16).all(|i| ptr[i] == i as u8 * 2));
+    assert_eq!(allocator.usable_size(&ptr), Ok(64));
+
+    // already aligned, so it can grow in place
+    let base = ptr.as_ptr() as *mut u8;
+    let ptr = allocator.realloc_aligned(ptr, 128, 32).unwrap();
+    assert_eq!(ptr.as_ptr() as *mut u8, base);
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+fn guard_is_not_live() {
+    let mut allocator = Valloc::with_guard_region(vec![0; 256].leak(), 16);
+    let a = allocator.alloc::<u8>(32).unwrap();
+    assert_eq!(allocator.oldest_live(), Some((a.as_ptr(), 32)));
+    assert_eq!(allocator.stats().live_chunks, 1);
+
+    allocator.free(a).unwrap();
+    assert!(allocator.leak_check().is_empty());
+    assert_eq!(allocator.oldest_live(), None);
+    assert_eq!(allocator.stats().live_chunks, 0);
+    assert!(allocator.is_empty());
+}
+
+#[test]
+fn guard_rejected_everywhere() {
+    let mut allocator = Valloc::with_guard_region(vec![0; 256].leak(), 16);
+    let guard = allocator.memory().as_ptr() as usize + 240;
+    let guard_ptr = || SmartPointer::<u8>::new(std::ptr::NonNull::new(guard as *mut u8).unwrap());
+    let not_allocated = Err(VallocError::NotAllocated { addr: guard });
+
+    assert_eq!(allocator.split_chunk(guard_ptr(), 8).map(|_| ()), not_allocated);
+    assert_eq!(allocator.shrink_in_place(&guard_ptr(), 8).map(|_| ()), not_allocated);
+    assert!(!allocator.grow_in_place(&guard_ptr(), 32));
+    let slice = SmartPointer::new(std::ptr::NonNull::slice_from_raw_parts(std::ptr::NonNull::new(guard as *mut u8).unwrap(), 16));
+    assert_eq!(allocator.realloc_array(slice, 4).map(|_| ()), not_allocated);
+
+    let last = allocator.alloc::<u8>(240).unwrap();
+    assert_eq!(allocator.join_chunks(last, guard_ptr()).map(|_| ()), not_allocated);
+    assert!(allocator.validate_all().is_empty());
+}
+
+#[test]
+fn empty_and_full() {
+    let mut allocator = Valloc::with_guard_region(vec![0; 128].leak(), 16);
+    assert!(allocator.is_empty() && !allocator.is_full());
+
+    let a = allocator.alloc::<[u8]>(64).unwrap();
+    assert!(!allocator.is_empty() && !allocator.is_full());
+    let b = allocator.alloc::<[u8]>(48).unwrap();
+    assert!(!allocator.is_empty() && allocator.is_full());
+
+    allocator.free(b).unwrap();
+    allocator.free(a).unwrap();
+    assert!(allocator.is_empty());
+}
+
+#[test]
+fn scope_reclaims() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let kept = allocator.alloc::<[u8]>(100).unwrap();
+    let before = allocator.available();
+    {
+        let mut scope = allocator.scope();
+        let a = scope.alloc::<[u8]>(200).unwrap();
+        let _b = scope.alloc::<[u8]>(300).unwrap();
+        let _a = scope.realloc(a, 400).unwrap();
+        assert!(scope.available() < before);
+    }
+    assert_eq!(allocator.available(), before);
+    assert!(allocator.owns(&kept));
+    allocator.free(kept).unwrap();
+    assert!(allocator.is_empty());
+}
+
+#[test]
+fn size_histogram() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    for size in [1, 3, 5, 100] {
+        allocator.alloc::<[u8]>(size).unwrap();
+    }
+
+    let histogram = allocator.size_histogram();
+    assert_eq!(&histogram[..8], &[1, 1, 1, 0, 0, 0, 1, 0]);
+    assert_eq!(histogram.iter().sum::<usize>(), allocator.stats().live_chunks);
+}
+
+#[test]
+fn merge_with_adjacent() {
+    let (left, right) = vec![0u8; 512].leak().split_at_mut(256);
+    let mut allocator = Valloc::new(left);
+    let mut other = Valloc::new(right);
+    let mut kept = allocator.alloc::<u32>(size_of::<u32>()).unwrap();
+    kept.write(7);
+    let theirs = other.alloc::<[u8]>(200).unwrap();
+    other.free(theirs).unwrap();
+
+    let Err(e) = allocator.merge_with(Valloc::new(vec![0; 64].leak())) else { panic!("merged a buffer from elsewhere") };
+    assert!(matches!(e, VallocError::NotAdjacent { .. }));
+
+    allocator.merge_with(other).unwrap();
+    assert_eq!(allocator.memory().len(), 512);
+    assert_eq!(allocator.available(), 512 - size_of::<u32>());
+    assert!(allocator.validate_all().is_empty());
+
+    // only fits across the old boundary
+    let spanning = allocator.alloc::<[u8]>(400).unwrap();
+    assert!((spanning.as_ptr() as *mut u8 as usize) < allocator.memory().as_ptr() as usize + 256);
+    assert_eq!(kept.read(), 7);
+    allocator.free(spanning).unwrap();
+    allocator.free(kept).unwrap();
+}
+
+#[test]
+fn merge_with_rejects_frozen_other() {
+    let (left, right) = vec![0u8; 512].leak().split_at_mut(256);
+    let mut allocator = Valloc::new(left);
+    let mut other = Valloc::new(right);
+    other.freeze();
+
+    assert_eq!(allocator.merge_with(other).err(), Some(VallocError::Frozen));
+    assert_eq!(allocator.memory().len(), 256);
+}
+
+#[test]
+fn merge_with_rejects_header() {
+    let (left, right) = vec![0u8; 2048].leak().split_at_mut(1024);
+    let mut allocator = Valloc::new(left);
+    let other = Valloc::new_in_place(right);
+
+    assert_eq!(allocator.merge_with(other).err(), Some(VallocError::UnexpectedHeader));
+    assert_eq!(allocator.memory().len(), 1024);
+}
+
+#[test]
+fn merge_with_rejects_other_page_size() {
+    let (left, right) = vec![0u8; 2048].leak().split_at_mut(1024);
+    let mut allocator = Valloc::with_page_size(left, 256);
+    let other = Valloc::new(right);
+
+    assert_eq!(allocator.merge_with(other).err(), Some(VallocError::PageSizeMismatch { ours: 256, theirs: 1 }));
+    assert_eq!(allocator.memory().len(), 1024);
+}
+
+#[test]
+fn merge_with_coalesces_old_guard() {
+    let (left, right) = vec![0u8; 512].leak().split_at_mut(256);
+    let mut allocator = Valloc::with_guard_region(left, 16);
+    let mut other = Valloc::new(right);
+    let _theirs = other.alloc::<u8>(64).unwrap();
+
+    allocator.merge_with(other).unwrap();
+    assert!(allocator.validate_all().is_empty());
+    assert_eq!(allocator.chunk_at_offset(0).map(|x| (x.size, x.in_use)), Some((256, false)));
+}
+
+#[test]
+fn fill_pool() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let blocks = allocator.fill::<[u8]>(100);
+    assert_eq!(blocks.len(), 10);
+    assert_eq!(allocator.available(), 24);
+    assert!(allocator.fill::<[u8]>(0).is_empty());
+
+    for block in blocks { allocator.free(block).unwrap(); }
+}
+
+#[test]
+fn chunk_at_offset() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let _pad = allocator.alloc::<[u8]>(16).unwrap();
+    let ptr = allocator.alloc::<[u8]>(64).unwrap();
+
+    let span = Span { offset: 16, size: 64, in_use: true };
+    assert_eq!(allocator.chunk_at_offset(40), Some(span));
+    assert_eq!(allocator.chunk_at_offset(16), Some(span));
+    assert_eq!(allocator.chunk_at_offset(79), Some(span));
+    assert_eq!(allocator.chunk_at_offset(80), Some(Span { offset: 80, size: 176, in_use: false }));
+    assert_eq!(allocator.chunk_at_offset(256), None);
+    allocator.free(ptr).unwrap();
+}
+
+#[test]
+fn defragment_is_stable() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let mut a = allocator.alloc::<u64>(size_of::<u64>()).unwrap();
+    let b = allocator.alloc::<[u8]>(32).unwrap();
+    let mut c = allocator.alloc::<u64>(size_of::<u64>()).unwrap();
+    a.write(1);
+    c.write(3);
+    allocator.free(b).unwrap();
+
+    let remap = allocator.defragment();
+    let moved = |ptr: *mut u8| remap.iter().find(|(old, _)| *old == ptr).map_or(ptr, |(_, new)| *new);
+    let (a, c) = (moved(a.as_ptr().cast()), moved(c.as_ptr().cast()));
+    assert!(a < c);
+    assert_eq!(unsafe { (a.cast::<u64>().read(), c.cast::<u64>().read()) }, (1, 3));
+    assert_eq!(c as usize - a as usize, size_of::<u64>());
+}
+
+#[test]
+fn defragment_keeps_alignment() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let _byte = allocator.alloc::<u8>(1).unwrap();
+    let gap = allocator.alloc::<u8>(99).unwrap();
+    let array = allocator.alloc_array::<u64>(4).unwrap();
+    let odd = allocator.alloc::<u8>(5).unwrap();
+    let wide = allocator.alloc_aligned::<u8>(16, 64).unwrap();
+    unsafe { array.as_ptr().cast::<u64>().write(0xABCD); }
+    allocator.free(gap).unwrap();
+    allocator.free(odd).unwrap();
+
+    // nothing lands on an address its allocation wasn't aligned to, however odd the holes before it
+    let remap = allocator.defragment();
+    assert_eq!(remap.len(), 2);
+    for (old, new) in &remap {
+        let align = allocator.chunks().iter().find(|x| x.get_ptr::<u8>() == *new).unwrap().get_align();
+        assert_eq!(*new as usize % align, 0, "{old:?} moved to {new:?}, aligned to {align}");
+    }
+    let moved = |ptr: *mut u8| remap.iter().find(|(old, _)| *old == ptr).map_or(ptr, |(_, new)| *new);
+    let array = moved(array.as_ptr().cast());
+    assert_eq!(array as usize % std::mem::align_of::<u64>(), 0);
+    assert_eq!(unsafe { array.cast::<u64>().read() }, 0xABCD);
+    assert_eq!(moved(wide.as_ptr()) as usize % 64, 0);
+    assert!(allocator.validate_all().is_empty());
+}
+
+#[test]
+fn drain_free_list() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    allocator.set_coalesce_on_free(false);
+    let ptrs: Vec<SmartPointer<[u8]>> = (0..16).map(|_| allocator.alloc(32).unwrap()).collect();
+    // free everything but every fourth chunk, in runs of three
+    for (i, ptr) in ptrs.into_iter().enumerate() {
+        if i % 4 != 3 { allocator.free(ptr).unwrap(); }
+    }
+    assert_eq!(allocator.stats().free_chunks, 13);
+
+    // four runs between the live chunks and the untouched tail
+    assert_eq!(allocator.drain_free_list(), 5);
+    assert_eq!(allocator.stats().free_chunks, 5);
+    assert!(allocator.validate_all().is_empty());
+}
+
+#[test]
+fn validate_typed() {
+    let mut allocator = Valloc::new(vec![0; 64].leak());
+    let byte = allocator.alloc::<u8>(1).unwrap();
+    let word = allocator.alloc::<[u8]>(8).unwrap();
+    let end = byte.as_ptr() as usize + 1;
+
+    assert_eq!(allocator.validate_typed::<u8>(&byte), Ok(()));
+    assert_eq!(allocator.validate_typed::<u32>(&byte), Err(VallocError::OutOfBounds { addr: end }));
+    assert_eq!(allocator.validate_typed::<u64>(&word.cast()), Ok(()));
+    allocator.free(word).unwrap();
+    allocator.free(byte).unwrap();
+    let stale = SmartPointer::try_new(end as *mut u8).unwrap();
+    assert_eq!(allocator.validate_typed::<u8>(&stale), Err(VallocError::NotAllocated { addr: end }));
+}
+
+#[test]
+fn available_aligned() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let base = allocator.memory().as_ptr() as usize;
+    let ptrs: Vec<SmartPointer<[u8]>> = (0..10).map(|i| allocator.alloc(37 + i * 11).unwrap()).collect();
+    for ptr in ptrs.into_iter().step_by(2) { allocator.free(ptr).unwrap(); }
+
+    let brute_force: usize = allocator.chunk_list_repr().chunks.iter()
+        .filter(|x| !x.in_use)
+        .map(|x| x.size.saturating_sub(Valloc::align_offset(base + x.offset, 64)))
+        .sum();
+    assert_eq!(allocator.available_aligned(64), brute_force);
+    assert!(allocator.available_aligned(64) <= allocator.available());
+    assert_eq!(allocator.available_aligned(1), allocator.available());
+}
+
+#[test]
+fn chunk_from_offset() {
+    let memory = vec![0u8; 64].leak();
+    let base = memory.as_mut_ptr();
+    let chunk = ChunkNode::from_offset(base, 24, 8, true);
+    assert_eq!(chunk.get_ptr::<u8>(), base.wrapping_add(24));
+    assert_eq!(chunk.offset_from(base), 24);
+    assert_eq!((chunk.get_size(), chunk.is_in_use()), (8, true));
+}
+
+#[test]
+fn global_alloc_realloc_in_place() {
+    use std::alloc::{GlobalAlloc, Layout};
+
+    let allocator = crate::allocator::GlobalValloc::new(Valloc::new(vec![0; 1024].leak()));
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    unsafe {
+        let ptr = allocator.alloc(layout);
+        ptr.write_bytes(0xAB, 16);
+
+        // the rest of the memory is free so it grows right where it is
+        let grown = allocator.realloc(ptr, layout, 64);
+        assert_eq!(grown, ptr);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let shrunk = allocator.realloc(grown, layout, 32);
+        assert_eq!(shrunk, ptr);
+
+        // something in the way makes it move, the data comes along
+        let blocker = allocator.alloc(layout);
+        let layout = Layout::from_size_align(32, 8).unwrap();
+        let moved = allocator.realloc(shrunk, layout, 256);
+        assert_ne!(moved, ptr);
+        assert!(std::slice::from_raw_parts(moved, 16).iter().all(|&x| x == 0xAB));
+        assert!(allocator.realloc(moved, Layout::from_size_align(256, 8).unwrap(), 4096).is_null());
+        // a pointer from somewhere else is refused instead of panicking
+        let mut foreign = [0u8; 32];
+        assert!(allocator.realloc(foreign.as_mut_ptr(), layout, 64).is_null());
+
+        allocator.dealloc(moved, Layout::from_size_align(256, 8).unwrap());
+        allocator.dealloc(blocker, Layout::from_size_align(64, 8).unwrap());
+    }
+}
+
+#[test]
+fn alloc_near_hint() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let base = allocator.memory().as_ptr() as usize;
+    let mut ptrs: Vec<Option<SmartPointer<[u8]>>> = (0..16).map(|_| allocator.alloc(64).ok()).collect();
+    // holes at offsets 64 and 512
+    allocator.free(ptrs[1].take().unwrap()).unwrap();
+    allocator.free(ptrs[8].take().unwrap()).unwrap();
+
+    let a = allocator.alloc_near::<[u8]>(16, 520).unwrap();
+    let b = allocator.alloc_near::<[u8]>(16, 520).unwrap();
+    let offsets = [a.as_ptr() as *mut u8 as usize - base, b.as_ptr() as *mut u8 as usize - base];
+    assert_eq!(offsets, [512, 528]);
+
+    // without a hint the first hole is used
+    let c = allocator.alloc::<[u8]>(16).unwrap();
+    assert_eq!(c.as_ptr() as *mut u8 as usize - base, 64);
+    // a hint past the end picks the hole closest to the end
+    let d = allocator.alloc_near::<[u8]>(16, 4096).unwrap();
+    assert_eq!(d.as_ptr() as *mut u8 as usize - base, 544);
+}
+
+#[test]
+fn observer_sees_realloc_moves() {
+    let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let log = events.clone();
+    allocator.set_observer(Box::new(move |event| log.borrow_mut().push(event)));
+
+    let a = allocator.alloc::<[u8]>(16).unwrap();
+    let a_ptr = a.as_ptr() as *mut u8;
+    // the rest of the memory is free right after it
+    let a = allocator.realloc(a, 32).unwrap();
+    let b = allocator.alloc::<[u8]>(16).unwrap();
+    let a = allocator.realloc(a, 64).unwrap();
+    let moved_to = a.as_ptr() as *mut u8;
+    allocator.free(a).unwrap();
+    allocator.clear_observer();
+    allocator.free(b).unwrap();
+
+    let events = events.borrow();
+    let moves: Vec<bool> = events.iter().filter_map(|x| match x {
+        AllocEvent::Realloc { moved, .. } => Some(*moved),
+        _ => None,
+    }).collect();
+    assert_eq!(moves, [false, true]);
+    assert_eq!(events[0], AllocEvent::Alloc { ptr: a_ptr, size: 16 });
+    assert_eq!(events[3], AllocEvent::Realloc { old: a_ptr, new: moved_to, size: 64, moved: true });
+    // the allocation and free inside of the moving realloc are not reported on their own
+    assert_eq!(events.len(), 5);
+    assert!(matches!(events[4], AllocEvent::Free { ptr, .. } if ptr == moved_to));
+}
+
+#[test]
+fn observer_sees_realloc_to_zero_as_free() {
+    let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let log = events.clone();
+    allocator.set_observer(Box::new(move |event| log.borrow_mut().push(event)));
+
+    let a = allocator.alloc::<[u8]>(48).unwrap();
+    let a_ptr = a.as_ptr() as *mut u8;
+    allocator.realloc(a, 0).unwrap();
+
+    // no move to the dangling pointer that comes back, just the free
+    assert_eq!(*events.borrow(), [
+        AllocEvent::Alloc { ptr: a_ptr, size: 48 },
+        AllocEvent::Free { ptr: a_ptr, size: 48 },
+    ]);
+}
+
+#[test]
+fn try_alloc_array() {
+    let mut allocator = Valloc::new(vec![0; 256].leak()).with_name("hot");
+    assert!(allocator.try_alloc_array::<u32>(usize::MAX).is_none());
+    assert_eq!(allocator.alloc_array::<u32>(usize::MAX).err(), Some(VallocError::CapacityOverflow));
+
+    let array = allocator.try_alloc_array::<u32>(16).unwrap();
+    assert_eq!(array.len(), 16);
+    assert!(allocator.try_alloc_array::<u32>(64).is_none());
+
+    // growing an array past what a usize can count is reported the same way
+    let copy = SmartPointer::new(array.non_null_ptr());
+    assert_eq!(allocator.realloc_array::<u32>(copy, usize::MAX).err(), Some(VallocError::CapacityOverflow));
+    assert_eq!(array.len(), 16);
+    allocator.free(array).unwrap();
+}
+
+#[test]
+fn realloc_regrows_into_its_freed_tail() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let ptr = allocator.alloc::<[u8]>(24).unwrap();
+    let _next = allocator.alloc::<[u8]>(16).unwrap();
+    let base = ptr.as_ptr() as *mut u8;
+    let available = allocator.available();
+
+    // any shrink gives the tail back to the free list
+    let ptr = allocator.realloc(ptr, 16).unwrap();
+    assert_eq!(allocator.usable_size(&ptr), Ok(16));
+    assert_eq!(allocator.available(), available + 8);
+
+    // and growing again takes it back without moving
+    let ptr = allocator.realloc(ptr, 20).unwrap();
+    assert_eq!(ptr.as_ptr() as *mut u8, base);
+    assert_eq!(allocator.relocations(), 0);
+    assert_eq!(allocator.usable_size(&ptr), Ok(20));
+}
+
+#[test]
+fn realloc_grows_into_free_neighbor() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let mut ptr = allocator.alloc::<[u8]>(16).unwrap();
+    let next = allocator.alloc::<u8>(32).unwrap();
+    let _blocker = allocator.alloc::<u8>(8).unwrap();
+    ptr.fill(7);
+    let base = ptr.as_ptr() as *mut u8;
+    allocator.free(next).unwrap();
+
+    let ptr = allocator.realloc(ptr, 40).unwrap();
+    assert_eq!(ptr.as_ptr() as *mut u8, base);
+    assert_eq!(allocator.last_realloc_copied_bytes(), 0);
+    assert_eq!(allocator.relocations(), 0);
+    assert_eq!(allocator.usable_size(&ptr), Ok(40));
+    assert!(ptr[..16].iter().all(|&b| b == 7));
+
+    // what is left of the neighbor is still free
+    let rest = allocator.alloc::<u8>(8).unwrap();
+    assert_eq!(rest.as_ptr(), base.wrapping_add(40));
+}
+
+#[test]
+fn realloc_shrink_splits_tail() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let ptr = allocator.alloc::<[u8]>(64).unwrap();
+    let _next = allocator.alloc::<u8>(16).unwrap();
+    let base = ptr.as_ptr() as *mut u8;
+    let available = allocator.stats().available;
+
+    let ptr = allocator.realloc(ptr, 16).unwrap();
+    assert_eq!(ptr.as_ptr() as *mut u8, base);
+    assert_eq!(allocator.last_realloc_copied_bytes(), 0);
+    assert_eq!(allocator.usable_size(&ptr), Ok(16));
+    assert_eq!(allocator.stats().available, available + 48);
+
+    // the tail is a free chunk of its own
+    let tail = allocator.alloc::<u8>(48).unwrap();
+    assert_eq!(tail.as_ptr(), base.wrapping_add(16));
+}
+
+#[test]
+fn realloc_falls_back_to_copy() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let mut ptr = allocator.alloc::<[u8]>(16).unwrap();
+    let _next = allocator.alloc::<u8>(8).unwrap();
+    ptr.fill(3);
+    let base = ptr.as_ptr() as *mut u8;
+
+    // the chunk after it is in use, so the data has to move
+    let ptr = allocator.realloc(ptr, 32).unwrap();
+    assert_ne!(ptr.as_ptr() as *mut u8, base);
+    assert_eq!(allocator.last_realloc_copied_bytes(), 16);
+    assert_eq!(allocator.relocations(), 1);
+    assert!(ptr[..16].iter().all(|&b| b == 3));
+}
+
+#[test]
+fn live_tagged() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let a = allocator.alloc_tagged::<u8>(16, "mesh").unwrap();
+    let b = allocator.alloc::<u8>(8).unwrap();
+    let c = allocator.alloc_tagged::<u8>(32, "audio").unwrap();
+
+    let live: Vec<_> = allocator.live_tagged().collect();
+    assert_eq!(live, vec![
+        (a.as_ptr(), 16, Some("mesh")),
+        (b.as_ptr(), 8, None),
+        (c.as_ptr(), 32, Some("audio")),
+    ]);
+
+    // a reused chunk doesn't inherit the old tag
+    allocator.free(a).unwrap();
+    let d = allocator.alloc::<u8>(16).unwrap();
+    assert!(allocator.live_tagged().any(|(ptr, _, tag)| ptr == d.as_ptr() && tag.is_none()));
+}
+
+#[test]
+fn shrink_in_place_reports_freed() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let ptr = allocator.alloc::<[u8]>(100).unwrap();
+    let available = allocator.available();
+
+    assert_eq!(allocator.shrink_in_place(&ptr, 40), Ok(60));
+    assert_eq!(allocator.available(), available + 60);
+    assert_eq!(allocator.usable_size(&ptr), Ok(40));
+    // already small enough
+    assert_eq!(allocator.shrink_in_place(&ptr, 40), Ok(0));
+    assert!(allocator.shrink_in_place(&ptr, 0).is_err());
+}
+
+#[test]
+fn compact_all_rewrites_pointers() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let mut live = Vec::new();
+    let mut holes = Vec::new();
+    for i in 0..6u8 {
+        let mut ptr = allocator.alloc::<u8>(16).unwrap();
+        *ptr = i;
+        if i % 2 == 0 { holes.push(ptr) } else { live.push(ptr) }
+    }
+    for hole in holes.into_iter().rev() {
+        allocator.free(hole).unwrap();
+    }
+
+    let available = allocator.available();
+    assert_eq!(allocator.compact_all(&mut live), available);
+    for (ptr, i) in live.iter().zip([1, 3, 5]) {
+        assert_eq!(**ptr, i);
+        assert_eq!(allocator.usable_size(ptr), Ok(16));
+    }
+}
+
+#[test]
+fn from_slice_copy() {
+    let data: Vec<u8> = (0..64).collect();
+    let mut allocator = Valloc::from_slice_copy(&data);
+    assert_eq!(allocator.read_at_offset::<[u8; 4]>(20), Ok([20, 21, 22, 23]));
+    assert!(allocator.is_full());
+    assert!(allocator.alloc::<u8>(1).is_err());
+
+    // the copy is owned, the original can go away
+    drop(data);
+    let blob = allocator.chunk_at_offset(0).unwrap();
+    assert!(blob.in_use && blob.size == 64);
+    assert_eq!(allocator.read_at_offset::<u8>(63), Ok(63));
+}
+
+#[test]
+fn mark_in_use_reserves_region() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let reserved = allocator.mark_in_use(96, 64).unwrap();
+    let range = reserved.as_ptr() as usize..reserved.as_ptr() as usize + 64;
+    assert!(allocator.mark_in_use(128, 8).is_err());
+
+    // everything around the region can still be handed out, but never the region itself
+    for ptr in allocator.fill::<u8>(16) {
+        let start = ptr.as_ptr() as usize;
+        assert!(start + 16 <= range.start || start >= range.end);
+    }
+    assert_eq!(allocator.available(), 0);
+
+    allocator.freeze();
+    assert_eq!(allocator.mark_free(96, 64), Err(VallocError::Frozen));
+    assert_eq!(allocator.mark_free(0, 1), Err(VallocError::Frozen));
+    assert_eq!(allocator.available(), 0);
+    allocator.thaw();
+
+    allocator.mark_free(96, 64).unwrap();
+    assert_eq!(allocator.available(), 64);
+    assert!(allocator.mark_free(96, 64).is_err());
+}
+
+#[test]
+fn realloc_copies_at_most_both_sizes() {
+    let mut allocator = Valloc::new(vec![0; 512].leak());
+    // offset 1 is never 64 aligned, so the shrink has to move
+    let pad = allocator.alloc::<u8>(1).unwrap();
+    let ptr = allocator.alloc::<[u8]>(100).unwrap();
+    let ptr = allocator.realloc_aligned(ptr, 30, 64).unwrap();
+    assert_eq!(allocator.last_realloc_copied_bytes(), 30);
+
+    // something right after it keeps it from growing in place
+    let end = ptr.as_ptr() as *mut u8 as usize - pad.as_ptr() as usize + 30;
+    allocator.mark_in_use(end, 1).unwrap();
+    let ptr = allocator.realloc(ptr, 100).unwrap();
+    assert_eq!(allocator.last_realloc_copied_bytes(), 30);
+
+    // shrinking in place doesn't copy anything
+    allocator.realloc(ptr, 30).unwrap();
+    assert_eq!(allocator.last_realloc_copied_bytes(), 0);
+}
+
+#[test]
+fn new_in_place_roundtrip() {
+    let mut memory = vec![0u8; 1024];
+    let mut allocator = Valloc::new_in_place(&mut memory);
+    let mut a = allocator.alloc::<u32>(4).unwrap();
+    *a = 0xdead_beef;
+    let b = allocator.alloc::<u8>(32).unwrap();
+    allocator.free(b).unwrap();
+    let offset = a.as_ptr() as usize - allocator.memory().as_ptr() as usize;
+
+    let mut copy = allocator.memory().to_vec();
+    let mut reopened = Valloc::open_in_place(&mut copy).unwrap();
+    assert_eq!(reopened.chunk_list_repr().chunks, allocator.chunk_list_repr().chunks);
+    assert_eq!(reopened.read_at_offset::<u32>(offset), Ok(0xdead_beef));
+    assert!(reopened.alloc::<u8>(64).is_ok());
+
+    let mut blank = vec![0u8; 1024];
+    assert_eq!(Valloc::open_in_place(&mut blank).err(), Some(VallocError::MissingHeader));
+}
+
+#[test]
+fn new_in_place_header_stays_current() {
+    let mut memory = vec![0u8; 1024];
+    let mut allocator = Valloc::new_in_place(&mut memory);
+    assert!(allocator.is_empty());
+    assert!(allocator.leak_check().is_empty());
+
+    // the header is never handed out or freed
+    let header = SmartPointer::new(std::ptr::NonNull::new(allocator.memory().as_ptr() as *mut u8).unwrap());
+    let addr = header.as_ptr() as usize;
+    assert!(!allocator.owns(&header));
+    assert_eq!(allocator.free(header), Err(VallocError::NotAllocated { addr }));
+
+    let mut ptrs: Vec<_> = (1..=6).map(|i| allocator.alloc::<u8>(i * 8).unwrap()).collect();
+    allocator.free(ptrs.remove(1)).unwrap();
+    allocator.free(ptrs.remove(3)).unwrap();
+    assert_eq!(allocator.stats().live_chunks, 4);
+
+    // no need to store anything, every change is already in the header
+    let mut copy = allocator.memory().to_vec();
+    let reopened = Valloc::open_in_place(&mut copy).unwrap();
+    assert_eq!(reopened.chunk_list_repr().chunks, allocator.chunk_list_repr().chunks);
+    assert_eq!(reopened.leak_check().len(), 4);
+
+    for ptr in ptrs {
+        allocator.free(ptr).unwrap();
+    }
+    allocator.reset();
+    assert!(allocator.is_empty());
+    assert!(allocator.validate_all().is_empty());
+}
+
+#[test]
+fn new_in_place_header_full() {
+    use crate::validate::ConsistencyIssue;
+
+    let mut memory = vec![0u8; 1024];
+    let mut allocator = Valloc::new_in_place_with_capacity(&mut memory, 5);
+    let _a = allocator.alloc::<u8>(8).unwrap();
+    let b = allocator.alloc::<u8>(8).unwrap();
+    let _c = allocator.alloc::<u8>(8).unwrap();
+    allocator.free(b).unwrap();
+    assert!(allocator.validate_all().is_empty());
+
+    // six chunks don't fit in a header for five
+    let _d = allocator.alloc::<u8>(16).unwrap();
+    assert_eq!(allocator.validate_all(), vec![ConsistencyIssue::HeaderFull { chunks: 6, capacity: 5 }]);
+    let mut copy = allocator.memory().to_vec();
+    assert_eq!(Valloc::open_in_place(&mut copy).err(), Some(VallocError::HeaderFull { chunks: 6, capacity: 5 }));
+}
+
+#[test]
+fn with_region_gives_space_back() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let _kept = allocator.alloc::<u8>(16).unwrap();
+    let available = allocator.available();
+
+    let used = allocator.with_region(128, |region| {
+        assert_eq!(region.available(), 128);
+        let a = region.alloc::<[u8]>(100).unwrap();
+        assert!(region.alloc::<[u8]>(64).is_err());
+        a.len()
+    }).unwrap();
+    assert_eq!(used, 100);
+    assert_eq!(allocator.available(), available);
+
+    assert!(allocator.with_region(1024, |_| ()).is_err());
+}
+
+#[test]
+fn zero_on_alloc() {
+    let mut allocator = Valloc::new(vec![0; 128].leak());
+    let mut dirty = allocator.alloc_array::<u8>(64).unwrap();
+    dirty.fill(0xaa);
+    allocator.free(dirty).unwrap();
+
+    allocator.set_zero_on_alloc(true);
+    let bytes = allocator.alloc::<[u8]>(32).unwrap();
+    assert!(bytes.iter().all(|&x| x == 0));
+    let array = allocator.alloc_array::<u32>(8).unwrap();
+    assert!(array.iter().all(|&x| x == 0));
+}
+
+#[test]
+fn search_len() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    allocator.set_coalesce_on_free(false);
+    let small: Vec<SmartPointer<u8>> = (0..10).map(|_| allocator.alloc::<u8>(8).unwrap()).collect();
+    for ptr in small {
+        allocator.free(ptr).unwrap();
+    }
+
+    // ten free chunks that are too small come before the one that fits
+    let scanned = allocator.total_scanned();
+    allocator.alloc::<u8>(64).unwrap();
+    assert_eq!(allocator.last_search_len(), 11);
+    allocator.alloc::<u8>(8).unwrap();
+    assert_eq!(allocator.last_search_len(), 1);
+    assert_eq!(allocator.total_scanned(), scanned + 11 + 1);
+}
+
+#[test]
+fn errors_dont_allocate() {
+    fn assert_copy<T: Copy + 'static>() {}
+    assert_copy::<VallocError>();
+
+    let mut allocator = Valloc::new(vec![0; 64].leak()).with_name("audio");
+    let before = heap_allocs();
+    let err = allocator.alloc::<u8>(128).err().unwrap();
+    let copy = err;
+    assert_eq!(heap_allocs(), before);
+    assert_eq!(err, copy);
+}
+
+// a backtrace is captured on every allocation with the `backtrace` feature, which does allocate
+#[test]
+#[cfg(not(feature = "backtrace"))]
+fn chunk_nodes_are_recycled() {
+    let mut allocator = Valloc::new(vec![0; 1024].leak());
+    let churn = |allocator: &mut Valloc| {
+        let a = allocator.alloc::<u8>(16).unwrap();
+        let b = allocator.alloc_aligned::<u8>(24, 64).unwrap();
+        let (c, d) = allocator.split_chunk(b, 8).unwrap();
+        allocator.free(a).unwrap();
+        let cd = allocator.join_chunks(c, d).unwrap();
+        let cd = allocator.realloc(cd, 200).unwrap();
+        let cd = allocator.realloc(cd, 8).unwrap();
+        allocator.free(cd).unwrap();
+    };
+
+    // the first round creates the nodes, after that every split reuses one that a merge gave back
+    churn(&mut allocator);
+    let before = heap_allocs();
+    for _ in 0..10 {
+        churn(&mut allocator);
+    }
+    assert_eq!(heap_allocs(), before);
+    assert_eq!(allocator.chunk_list_repr().chunks.len(), 1);
+}
+
+#[test]
+fn alloc_pages() {
+    const PAGE: usize = 256;
+    let mut allocator = Valloc::with_page_size(vec![0; 16 * PAGE].leak(), PAGE);
+    assert_eq!(allocator.page_size(), PAGE);
+    let _byte = allocator.alloc::<u8>(1).unwrap();
+
+    let pages = allocator.alloc_pages(3).unwrap();
+    assert_eq!(pages.as_ptr() as *mut u8 as usize % PAGE, 0);
+    assert_eq!(pages.len(), 3 * PAGE);
+    let available = allocator.available();
+
+    allocator.free(pages).unwrap();
+    assert_eq!(allocator.available(), available + 3 * PAGE);
+}
+
+#[test]
+fn defragment_budgeted() {
+    let mut allocator = Valloc::new(vec![0; 512].leak());
+    let ptrs: Vec<SmartPointer<u8>> = (0..8).map(|_| allocator.alloc::<u8>(32).unwrap()).collect();
+    let mut kept = Vec::new();
+    for (i, ptr) in ptrs.into_iter().enumerate().rev() {
+        if i % 2 == 0 { allocator.free(ptr).unwrap(); } else { kept.push(ptr); }
+    }
+
+    // the four chunks left in use have to move, 64 bytes at a time is two of them per call
+    let (mut total, mut calls) = (0, 0);
+    loop {
+        let (moved, done) = allocator.defragment_budgeted(64);
+        assert!(moved <= 64);
+        total += moved;
+        calls += 1;
+        if done { break; }
+    }
+    assert_eq!((total, calls), (128, 2));
+    assert_eq!(allocator.stats().largest_free, allocator.available());
+    assert_eq!(allocator.defragment_budgeted(64), (0, true));
+}
+
+#[test]
+fn alloc_at_offset() {
+    let mut allocator = Valloc::new(vec![0; 128].leak());
+    let (first, at) = allocator.alloc_at_offset::<[u8]>(24).unwrap();
+    let (second, next) = allocator.alloc_at_offset::<u8>(8).unwrap();
+    assert_eq!((at, next), (0, 24));
+    assert_eq!(allocator.chunk_at_offset(next).map(|x| x.offset), Some(24));
+    assert_eq!(second.as_ptr() as usize - first.as_ptr() as *mut u8 as usize, 24);
+}
+
+#[test]
+fn alloc_aligned_u64() {
+    let mut allocator = Valloc::new(vec![0; 64].leak());
+    let _odd = allocator.alloc::<u8>(1).unwrap();
+    for _ in 0..3 {
+        let mut value = allocator.alloc_aligned::<u64>(8, 8).unwrap();
+        assert_eq!(value.as_ptr() as usize % 8, 0);
+        *value = u64::MAX;
+        let _odd = allocator.alloc::<u8>(3).unwrap();
+    }
+    // the padding went back to the free list
+    assert!(allocator.alloc::<u8>(1).is_ok());
+}
+
+#[test]
+fn alloc_aligned_padding_doesnt_fit() {
+    let mut memory = vec![0u64; 4];
+    let bytes = unsafe { std::slice::from_raw_parts_mut(memory.as_mut_ptr() as *mut u8, 17) };
+    let mut allocator = Valloc::new(bytes);
+    let _odd = allocator.alloc::<u8>(1).unwrap();
+
+    // 16 bytes are free but after padding up to 8 only 9 are left
+    assert_eq!(allocator.available(), 16);
+    assert_eq!(allocator.alloc_aligned::<u8>(16, 8).err(), Some(VallocError::NoContiguousSpace { requested: 16, available: 16 }));
+    assert!(allocator.alloc_aligned::<u64>(8, 8).is_ok());
+}
+
+#[test]
+#[cfg(feature = "nightly")]
+fn merge_into_global() {
+    let _guard = lock_global();
+    let global = get_allocator();
+    // find a spot that is free in the global allocator
+    let probe = global.alloc_aligned::<u8>(64, 4).unwrap();
+    let offset = probe.as_ptr() as usize - global.memory().as_ptr() as usize;
+    global.free(probe).unwrap();
+
+    let memory = unsafe { std::slice::from_raw_parts_mut(global.memory().as_ptr() as *mut u8, global.memory().len()) };
+    let mut local = Valloc::new(memory);
+    let mut ptr = local.mark_in_use(offset, 64).unwrap().cast::<u32>();
+    *ptr = 7;
+    let available = global.available();
+
+    assert_eq!(Valloc::new(vec![0; 64].leak()).try_merge_into_global(), Err(VallocError::BackingMismatch));
+    local.try_merge_into_global().unwrap();
+    assert_eq!(global.available(), available - 64);
+    assert_eq!(global.read(&ptr), Ok(7));
+    global.free(ptr).unwrap();
+    assert_eq!(global.available(), available);
+}
+
+#[test]
+fn free_merges_backward() {
+    let mut allocator = Valloc::new(vec![0; 96].leak());
+    let a = allocator.alloc::<u8>(32).unwrap();
+    let b = allocator.alloc::<u8>(32).unwrap();
+    let _c = allocator.alloc::<u8>(32).unwrap();
+
+    // the middle first, then the one before it
+    allocator.free(b).unwrap();
+    allocator.free(a).unwrap();
+    assert_eq!(allocator.stats().largest_free, 64);
+    assert!(allocator.alloc::<[u8]>(64).is_ok());
+}
+
+#[test]
+fn double_free_after_merge_backward() {
+    let mut allocator = Valloc::new(vec![0; 96].leak());
+    let x = allocator.alloc::<u8>(32).unwrap();
+    let y = allocator.alloc::<u8>(32).unwrap();
+    let _z = allocator.alloc::<u8>(32).unwrap();
+    let copy = |ptr: &SmartPointer<u8>| SmartPointer::<u8>::new(ptr.non_null_ptr());
+    let (y_addr, inside) = (y.as_ptr() as usize, y.as_ptr().wrapping_add(8));
+
+    // `y` is merged into `x` and its chunk is gone, freeing it again is still a double free
+    allocator.free(x).unwrap();
+    allocator.free(copy(&y)).unwrap();
+    assert_eq!(allocator.free(copy(&y)), Err(VallocError::DoubleFree { addr: y_addr }));
+    let never = SmartPointer::<u8>::new(std::ptr::NonNull::new(inside).unwrap());
+    assert_eq!(allocator.free(never), Err(VallocError::NotAllocated { addr: y_addr + 8 }));
+
+    // once the memory is handed out again the old pointer means nothing anymore
+    let xy = allocator.alloc::<u8>(64).unwrap();
+    assert_eq!(allocator.free(copy(&y)), Err(VallocError::NotAllocated { addr: y_addr }));
+    allocator.free(xy).unwrap();
+}
+
+#[test]
+fn read_cstr() {
+    let mut allocator = Valloc::new(vec![0; 64].leak());
+    let mut text = allocator.alloc_array::<u8>(9).unwrap();
+    text.copy_from_slice(b"hi\0extra\0").unwrap();
+    let ptr = text.cast::<u8>();
+    assert_eq!(allocator.read_cstr(&ptr), Ok("hi"));
+
+    let mut bad = allocator.alloc_array::<u8>(3).unwrap();
+    bad.copy_from_slice(&[0xff, b'a', 0]).unwrap();
+    assert_eq!(allocator.read_cstr(&bad.cast::<u8>()), Err(VallocError::InvalidUtf8 { at: 0 }));
+}
+
+#[test]
+fn read_cstr_unterminated() {
+    let mut allocator = Valloc::new(vec![0; 64].leak());
+    let mut text = allocator.alloc_array::<u8>(5).unwrap();
+    text.copy_from_slice(b"hello").unwrap();
+    // the zeroes right after the allocation don't count
+    let end = text.as_ptr() as *mut u8 as usize + 5;
+    assert_eq!(allocator.read_cstr(&text.cast::<u8>()), Err(VallocError::Unterminated { end }));
+}
+
+#[test]
+fn chunk_list_stays_sorted() {
+    let mut allocator = Valloc::new(vec![0; 2048].leak());
+    let mut live: Vec<SmartPointer<u8>> = Vec::new();
+    for i in 0..64usize {
+        if let Ok(ptr) = allocator.alloc::<u8>(8 + i * 7 % 40) { live.push(ptr); }
+        if i % 3 == 2 { allocator.free(live.remove(i % live.len())).unwrap(); }
+
+        let ptrs: Vec<usize> = allocator.chunks().iter().map(|x| x.get_ptr::<u8>() as usize).collect();
+        assert!(ptrs.windows(2).all(|x| x[0] < x[1]));
+    }
+    allocator.chunks().assert_sorted();
+}
+
+#[test]
+fn free_by_tag() {
+    let mut allocator = Valloc::new(vec![0; 256].leak());
+    let scene: Vec<SmartPointer<u8>> = (0..3).map(|_| allocator.alloc_tagged::<u8>(32, "scene").unwrap()).collect();
+    let ui = allocator.alloc_tagged::<u8>(16, "ui").unwrap();
+    let _untagged = allocator.alloc::<u8>(8).unwrap();
+    let available = allocator.available();
+
+    assert_eq!(allocator.free_by_tag("scene"), 3);
+    assert_eq!(allocator.available(), available + 96);
+    assert!(scene.iter().all(|ptr| !allocator.owns(ptr)));
+    assert!(allocator.owns(&ui));
+    assert_eq!(allocator.live_tagged().filter(|(_, _, tag)| tag.is_some()).count(), 1);
+    // the scene chunks were merged into one
+    assert!(allocator.validate_all().is_empty());
+    assert_eq!(allocator.free_by_tag("scene"), 0);
+}
+
+#[test]
+fn alloc_zeroed_scrubs_reused_memory() {
+    use crate::ffi::vcalloc;
+
+    let mut allocator = Valloc::new(vec![0; 64].leak());
+    let mut garbage = allocator.alloc_array::<u8>(32).unwrap();
+    garbage.fill(0xcd);
+    let at = garbage.as_ptr() as *mut u8;
+    allocator.free(garbage).unwrap();
+
+    let zeroed = allocator.alloc_zeroed::<[u8]>(32).unwrap();
+    assert_eq!(zeroed.as_ptr() as *mut u8, at);
+    let bytes = unsafe { std::slice::from_raw_parts(at, 32) };
+    assert!(bytes.iter().all(|&x| x == 0));
+
+    // the size is checked before the global allocator is touched
+    assert!(vcalloc(usize::MAX, 2).is_null());
+}
@@ -0,0 +1,21 @@
+/// A broken invariant of a `Valloc` heap, found with `Valloc::validate_all`.
+/// 
+/// Every position is an offset from the start of the managed memory, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsistencyIssue {
+    /// Memory that is not covered by any chunk.
+    Gap { at: usize, size: usize },
+    /// The chunk at `b` starts before the chunk at `a` ends.
+    Overlap { a: usize, b: usize },
+    /// The tracked available size doesn't match the size of the free chunks.
+    AvailableMismatch { tracked: usize, actual: usize },
+    /// Two free chunks next to each other that should have been merged.
+    AdjacentFree { a: usize, b: usize },
+    /// A byte of the guard region of `Valloc::with_guard_region` is no longer zero.
+    GuardOverwritten { at: usize },
+    /// A chunk of `size` bytes at `at` that doesn't lie inside of the memory.
+    /// `at` wraps around if the chunk starts before the memory.
+    OutOfRange { at: usize, size: usize },
+    /// The header of `Valloc::new_in_place` has room for `capacity` chunks but the list has `chunks`.
+    HeaderFull { chunks: usize, capacity: usize },
+}
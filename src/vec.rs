@@ -0,0 +1,126 @@
+use crate::{allocator::{SmartPointer, Valloc}, error::VallocError};
+
+/// A growable array that keeps its elements in a `Valloc`.
+/// 
+/// This is a small stand-in for `Vec::new_in` that works on a stable compiler.
+/// The buffer is freed (and the elements dropped) when the `VVec` is dropped.
+/// 
+/// # Example
+/// 
+/// ```
+/// use valloc::{allocator::Valloc, vec::VVec};
+/// let mut allocator = Valloc::new(vec![0u8; 1024].leak());
+/// let mut v = VVec::new(&mut allocator);
+/// v.push(1u32).unwrap();
+/// v.push(2).unwrap();
+/// assert_eq!(v.get(1), Some(&2));
+/// ```
+pub struct VVec<'v, 'a, T> {
+    valloc: &'v mut Valloc<'a>,
+    buf: Option<SmartPointer<[T]>>,
+    len: usize,
+}
+
+impl<'v, 'a, T> VVec<'v, 'a, T> {
+    /// Create an empty `VVec`, nothing is allocated until the first `push`.
+    pub fn new(valloc: &'v mut Valloc<'a>) -> Self {
+        Self { valloc, buf: None, len: 0 }
+    }
+
+    /// Create an empty `VVec` with room for `capacity` elements.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(VVec)` - The new `VVec` if successful.
+    /// * `Err(VallocError)` - The reason the buffer could not be allocated.
+    pub fn with_capacity(valloc: &'v mut Valloc<'a>, capacity: usize) -> Result<Self, VallocError> {
+        let mut vec = Self::new(valloc);
+        if capacity > 0 { vec.grow_to(capacity)?; }
+        Ok(vec)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of elements that fit before the buffer has to grow.
+    pub fn capacity(&self) -> usize {
+        if std::mem::size_of::<T>() == 0 { return usize::MAX; }
+        self.buf.as_ref().map_or(0, |buf| buf.len())
+    }
+
+    /// Append an element, doubling the buffer with `realloc_array` if it is full.
+    /// 
+    /// # Returns
+    /// 
+    /// * `Ok(())` - If the element was added.
+    /// * `Err(VallocError)` - The reason the buffer could not grow, the `VVec` is left as it was.
+    pub fn push(&mut self, value: T) -> Result<(), VallocError> {
+        if self.len == self.capacity() {
+            self.grow_to((self.len * 2).max(4))?;
+        }
+        unsafe { self.as_mut_ptr().add(self.len).write(value); }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove the last element and return it, or `None` if it is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 { return None; }
+        self.len -= 1;
+        Some(unsafe { self.as_mut_ptr().add(self.len).read() })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.as_mut_slice().get_mut(index)
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+
+    fn as_ptr(&self) -> *const T {
+        self.buf.as_ref().map_or(std::ptr::NonNull::dangling().as_ptr(), |buf| buf.as_ptr() as *mut T)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut T {
+        self.as_ptr() as *mut T
+    }
+
+    // zero sized elements never need a buffer
+    fn grow_to(&mut self, capacity: usize) -> Result<(), VallocError> {
+        if std::mem::size_of::<T>() == 0 { return Ok(()); }
+        self.buf = Some(match self.buf.take() {
+            Some(buf) => {
+                let copy = SmartPointer::new(buf.non_null_ptr());
+                match self.valloc.realloc_array(buf, capacity) {
+                    Ok(buf) => buf,
+                    Err(e) => { self.buf = Some(copy); return Err(e); },
+                }
+            },
+            None => self.valloc.alloc_array(capacity)?,
+        });
+        Ok(())
+    }
+}
+
+impl<T> Drop for VVec<'_, '_, T> {
+    fn drop(&mut self) {
+        unsafe { std::ptr::drop_in_place(self.as_mut_slice()); }
+        if let Some(buf) = self.buf.take() {
+            self.valloc.free(buf).expect("VVec buffer was freed behind its back!");
+        }
+    }
+}
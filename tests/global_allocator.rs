@@ -0,0 +1,147 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System}, cell::{Cell, UnsafeCell}, sync::{atomic::{AtomicBool, Ordering}, Mutex}
+};
+
+use valloc::allocator::{GlobalValloc, Valloc};
+
+const ARENA_SIZE: usize = 1 << 22;
+
+static mut ARENA: [u8; ARENA_SIZE] = [0; ARENA_SIZE];
+
+thread_local! {
+    // set while this thread is inside of the valloc, whatever the chunk list allocates then goes to `System`
+    static INSIDE: Cell<bool> = const { Cell::new(false) };
+}
+
+// `GlobalValloc` is not `Sync` and its chunk list allocates from the global allocator as well,
+// so every call takes a spin lock and the allocations made while holding it go to the system allocator
+struct Installed {
+    locked: AtomicBool,
+    valloc: UnsafeCell<Option<GlobalValloc<'static>>>,
+}
+
+unsafe impl Sync for Installed {}
+
+#[global_allocator]
+static GLOBAL: Installed = Installed { locked: AtomicBool::new(false), valloc: UnsafeCell::new(None) };
+
+// the tests share the arena, one of them can't count on the space next to its buffer while another one runs
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn in_arena(ptr: *const u8) -> bool {
+    let start = std::ptr::addr_of!(ARENA) as usize;
+    (start..start + ARENA_SIZE).contains(&(ptr as usize))
+}
+
+impl Installed {
+    // runs `f` on the valloc, or returns `None` if this is a call the chunk list made from inside of it
+    fn with<R>(&self, f: impl FnOnce(&GlobalValloc<'static>) -> R) -> Option<R> {
+        if INSIDE.get() { return None; }
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            std::hint::spin_loop();
+        }
+        INSIDE.set(true);
+
+        let valloc = unsafe { &mut *self.valloc.get() };
+        let valloc = valloc.get_or_insert_with(|| {
+            GlobalValloc::new(Valloc::new(unsafe { &mut *std::ptr::addr_of_mut!(ARENA) }))
+        });
+        let result = f(valloc);
+
+        INSIDE.set(false);
+        self.locked.store(false, Ordering::Release);
+        Some(result)
+    }
+}
+
+unsafe impl GlobalAlloc for Installed {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.with(|valloc| valloc.alloc(layout)) {
+            Some(ptr) if !ptr.is_null() => ptr,
+            // the arena is full, or this is the chunk list allocating
+            _ => System.alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if !in_arena(ptr) { return System.dealloc(ptr, layout); }
+        self.with(|valloc| valloc.dealloc(ptr, layout));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if !in_arena(ptr) { return System.realloc(ptr, layout, new_size); }
+        match self.with(|valloc| valloc.realloc(ptr, layout, new_size)) {
+            Some(new) if !new.is_null() => new,
+            // moving it out of the arena, the old chunk is only freed once the data is copied
+            _ => {
+                let new = self.alloc(Layout::from_size_align_unchecked(new_size, layout.align()));
+                if !new.is_null() {
+                    std::ptr::copy_nonoverlapping(ptr, new, layout.size().min(new_size));
+                    self.dealloc(ptr, layout);
+                }
+                new
+            },
+        }
+    }
+}
+
+#[test]
+fn collections_live_in_the_arena() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let mut numbers: Vec<u64> = Vec::with_capacity(4);
+    assert!(in_arena(numbers.as_ptr().cast()));
+    for i in 0..10_000 {
+        numbers.push(i);
+    }
+    assert!(in_arena(numbers.as_ptr().cast()));
+    assert!(numbers.iter().copied().eq(0..10_000));
+
+    numbers.truncate(10);
+    numbers.shrink_to_fit();
+    assert!(in_arena(numbers.as_ptr().cast()));
+    assert_eq!(numbers, (0..10).collect::<Vec<_>>());
+
+    let text: String = (0..100).map(|i| i.to_string()).collect();
+    assert!(in_arena(text.as_ptr()));
+    assert!(text.starts_with("0123456789101112"));
+
+    let boxed = Box::new([7u8; 4096]);
+    assert!(in_arena(boxed.as_ptr()));
+    assert!(boxed.iter().all(|&x| x == 7));
+}
+
+#[test]
+fn vec_grows_in_place() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+
+    // shrinking gives the tail back, so the space right after the buffer is free to grow into
+    let mut numbers: Vec<u64> = Vec::with_capacity(1024);
+    numbers.shrink_to(4);
+    let base = numbers.as_ptr();
+    assert!(in_arena(base.cast()));
+    assert_eq!(numbers.capacity(), 4);
+
+    for i in 0..1000 {
+        numbers.push(i);
+    }
+    assert!(numbers.capacity() > 4);
+    assert_eq!(numbers.as_ptr(), base);
+    assert!(numbers.iter().copied().eq(0..1000));
+}
+
+#[test]
+fn many_threads() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let handles: Vec<_> = (0..8).map(|t| std::thread::spawn(move || {
+        let mut strings = Vec::new();
+        for i in 0..500 {
+            strings.push(format!("{t}-{i}"));
+            if i % 3 == 0 { strings.swap_remove(0); }
+        }
+        strings.len()
+    })).collect();
+
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), 333);
+    }
+}